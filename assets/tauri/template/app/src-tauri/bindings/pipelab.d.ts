@@ -0,0 +1,385 @@
+// Generated from the Rust message types by `cargo test --test bindings`, do not edit
+
+export type JsonValue = number | string | boolean | Array<JsonValue> | { [key in string]?: JsonValue } | null;
+
+/**
+ * Generic structure for incoming WebSocket messages
+ */
+export type IncomingMessage = { url: string, correlationId?: string, body?: JsonValue, };
+
+/**
+ * Generic structure for outgoing WebSocket responses
+ */
+export type ResponseMessage<T> = { url: string, correlationId: string | null, body: T, 
+/**
+ * Set on the final frame of a streamed response, see [`StreamMessage`]
+ */
+seq?: number, 
+/**
+ * `true` on the final frame of a streamed response
+ */
+end?: boolean, };
+
+/**
+ * Success response body. The handler output is flattened next to `success`,
+ * the same way the Electron handlers reply with e.g. `{ success: true, size: 42 }`
+ */
+export type SuccessBody<T> = { success: boolean, } & T;
+
+/**
+ * Stable reason sent as `code` in every [`ErrorBody`], so games don't have to
+ * match error messages
+ */
+export type ErrorCode = "NOT_FOUND" | "PERMISSION_DENIED" | "ALREADY_EXISTS" | "INVALID_BODY" | "UNSUPPORTED_PLATFORM" | "NOT_IMPLEMENTED" | "TIMEOUT" | "CANCELLED" | "STEAM_UNAVAILABLE" | "UNAUTHORIZED" | "SHUTTING_DOWN" | "INTERNAL";
+
+/**
+ * Error response body, built with [`ErrorBody::from_error`] for handler errors
+ */
+export type ErrorBody = { success: boolean, error: string, code: ErrorCode, details?: JsonValue, 
+/**
+ * Only in debug builds
+ */
+backtrace?: string, };
+
+/**
+ * Message pushed by the server without a matching request, e.g. `/window/resized`
+ */
+export type EventMessage<T> = { url: string, body: T, };
+
+/**
+ * Intermediate frame of a streamed response: `{ url, correlationId, seq, chunk }`
+ * or `{ url, correlationId, seq, progress }`. The final frame is the usual response
+ * with `seq` and `end: true`, and its body tells whether the request succeeded.
+ */
+export type StreamMessage<T> = { url: string, correlationId: string | null, 
+/**
+ * Position of the frame in the stream, starting at 0
+ */
+seq: number, } & T;
+
+export type Progress = { done: number, total: number | null, };
+
+/**
+ * Body of the `/session` event. A client that loses its connection can reconnect
+ * with `ws://127.0.0.1:{port}/?token=...&session={sessionId}`, or send `session`
+ * next to `token` in `/auth`, to get the events and replies it missed meanwhile.
+ */
+export type SessionEvent = { sessionId: string, 
+/**
+ * `false` when a new session started, e.g. the previous one expired
+ */
+resumed: boolean, 
+/**
+ * Events the client missed but that weren't kept, over `session.maxMissedEvents`
+ */
+dropped: number, 
+/**
+ * Correlation IDs of the replies the client missed but that weren't kept, over
+ * `session.maxMissedReplies`. These requests ran, but their result is lost.
+ */
+droppedReplies: Array<string>, };
+
+/**
+ * Body of `/cancel`
+ */
+export type CancelBody = { 
+/**
+ * ID of the request to abort
+ */
+correlationId: string, };
+
+/**
+ * Reply of routes that always fail, `never` in the bindings
+ */
+export type Never = never;
+
+/**
+ * Reply of `/engine`
+ */
+export type EngineReply = { 
+/**
+ * Always `"tauri"`; the Electron runtime answers `"electron"`
+ */
+engine: string, };
+
+/**
+ * Body of `/events/subscribe` and `/events/unsubscribe`
+ */
+export type TopicBody = { topic: string, };
+
+/**
+ * Body of `/exit`
+ */
+export type ExitBody = { 
+/**
+ * Exit code of the process, 0 by default
+ */
+code?: number, };
+
+/**
+ * Body of `/fs/copy`
+ */
+export type CopyBody = { source: string, destination: string, 
+/**
+ * `true` by default
+ */
+overwrite?: boolean, stream?: boolean, };
+
+/**
+ * Body of the routes that only take a path
+ */
+export type PathBody = { path: string, };
+
+/**
+ * Reply of `/fs/exist`
+ */
+export type ExistReply = { success: boolean, };
+
+/**
+ * Body of `/fs/file/read`
+ */
+export type ReadFileBody = { path: string, 
+/**
+ * Node encoding name, `utf8` by default
+ */
+encoding?: string, stream?: boolean, 
+/**
+ * Bytes read per chunk when streamed, at most 16 MiB
+ */
+chunkSize?: number, };
+
+/**
+ * Reply of `/fs/file/read`. Streamed reads reply with [`SizeReply`] instead.
+ */
+export type ReadFileReply = { content: string, };
+
+/**
+ * Body of `/fs/file/read/binary`
+ */
+export type ReadBinaryBody = { path: string, stream?: boolean, 
+/**
+ * Bytes read per chunk when streamed, at most 16 MiB
+ */
+chunkSize?: number, };
+
+/**
+ * Reply of `/fs/file/read/binary` to JSON clients. Binary clients get the bytes
+ * as the payload of a binary frame instead.
+ */
+export type ReadBinaryReply = { content: Array<number>, };
+
+/**
+ * Reply of `/fs/file/size`, and of streamed reads
+ */
+export type SizeReply = { size: number, };
+
+/**
+ * Body of `/fs/file/write`
+ */
+export type WriteFileBody = { path: string, 
+/**
+ * Not needed when the data is the payload of a binary frame
+ */
+contents?: string, 
+/**
+ * Node encoding name, `utf8` by default
+ */
+encoding?: string, 
+/**
+ * Node file flag; `a` and `a+` append
+ */
+flag?: string, append?: boolean, };
+
+/**
+ * Body of `/fs/file/write-base64`
+ */
+export type WriteBase64Body = { path: string, base64Data: string, flag?: string, };
+
+/**
+ * Body of `/fs/folder/create`
+ */
+export type CreateFolderBody = { path: string, recursive?: boolean, };
+
+/**
+ * One entry of a `/fs/list` reply, as built by the Electron `fs/list` handler
+ */
+export type ListEntry = { type: string, name: string, parent: string, path: string, };
+
+/**
+ * Reply of `/fs/list`
+ */
+export type ListReply = { list: Array<ListEntry>, };
+
+/**
+ * Body of `/fs/move`
+ */
+export type MoveBody = { source: string, destination: string, 
+/**
+ * `true` by default
+ */
+overwrite?: boolean, };
+
+/**
+ * Reply of `/infos`, with Node's names for the architecture and platform
+ */
+export type InfosReply = { 
+/**
+ * `x64`, `arm64`, `ia32`...
+ */
+arch: string, 
+/**
+ * `win32`, `darwin`, `linux`...
+ */
+platform: string, 
+/**
+ * Version of the app
+ */
+version: string, };
+
+export type LogLevel = "error" | "warn" | "info" | "debug" | "trace";
+
+/**
+ * Body of `/log`
+ */
+export type LogBody = { 
+/**
+ * `info` by default
+ */
+level?: LogLevel, message: string, 
+/**
+ * Context written after the message, with the `redactFields` hidden
+ */
+fields?: { [key in string]?: JsonValue }, };
+
+/**
+ * Body of `/paths`
+ */
+export type PathsBody = { 
+/**
+ * One of [`PATH_NAMES`]
+ */
+name: string, };
+
+/**
+ * Reply of `/paths`
+ */
+export type PathsReply = { 
+/**
+ * The folder, using forward slashes
+ */
+data: string, };
+
+/**
+ * Body of `/run`
+ */
+export type RunBody = { command: string, args?: Array<string>, cwd?: string, 
+/**
+ * Added to the runtime's own environment
+ */
+env?: { [key in string]?: string }, stream?: boolean, };
+
+/**
+ * Reply of `/run`. The output is left out when it was streamed.
+ */
+export type RunReply = { stdout?: string, stderr?: string, 
+/**
+ * `null` when the process was killed by a signal
+ */
+code: number | null, };
+
+/**
+ * Body of `/window/close-response`
+ */
+export type CloseResponseBody = { 
+/**
+ * Whether the window may close
+ */
+allow: boolean, };
+
+/**
+ * Body of the routes that set a single value
+ */
+export type ValueBody<T> = { value: T, };
+
+/**
+ * Value of `/window/set-fullscreen`
+ */
+export type FullscreenMode = "fullscreen" | "normal";
+
+/**
+ * Body of `/window/set-ignore-mouse-events`
+ */
+export type IgnoreMouseEventsBody = { ignore: boolean, 
+/**
+ * Accepted for Electron compatibility, has no effect
+ */
+forward?: boolean, };
+
+/**
+ * Width and height in logical pixels
+ */
+export type Size = { width: number, height: number, };
+
+/**
+ * Position in logical pixels
+ */
+export type Position = { x: number, y: number, };
+
+/**
+ * Monitor the window is on
+ */
+export type MonitorState = { name: string | null, size: Size, position: Position, scaleFactor: number, };
+
+/**
+ * Reply of `/window/state`
+ */
+export type WindowState = { innerSize: Size, outerSize: Size, position: Position, scaleFactor: number, maximized: boolean, minimized: boolean, fullscreen: boolean, focused: boolean, visible: boolean, monitor: MonitorState | null, };
+
+/** Body and reply of each route. Replies are flattened into `SuccessBody`. */
+export interface Routes {
+  "/cancel": { body: CancelBody; reply: {} };
+  "/dialog/folder": { body: JsonValue; reply: Never };
+  "/dialog/open": { body: JsonValue; reply: Never };
+  "/dialog/save": { body: JsonValue; reply: Never };
+  "/discord/set-activity": { body: JsonValue; reply: Never };
+  "/engine": { body: {}; reply: EngineReply };
+  "/events/subscribe": { body: TopicBody; reply: {} };
+  "/events/unsubscribe": { body: TopicBody; reply: {} };
+  "/exit": { body: ExitBody; reply: {} };
+  "/fs/copy": { body: CopyBody; reply: {} };
+  "/fs/delete": { body: PathBody; reply: {} };
+  "/fs/exist": { body: PathBody; reply: ExistReply };
+  "/fs/file/read": { body: ReadFileBody; reply: ReadFileReply };
+  "/fs/file/read/binary": { body: ReadBinaryBody; reply: ReadBinaryReply };
+  "/fs/file/size": { body: PathBody; reply: SizeReply };
+  "/fs/file/write": { body: WriteFileBody; reply: {} };
+  "/fs/file/write-base64": { body: WriteBase64Body; reply: {} };
+  "/fs/folder/create": { body: CreateFolderBody; reply: {} };
+  "/fs/list": { body: PathBody; reply: ListReply };
+  "/fs/move": { body: MoveBody; reply: {} };
+  "/infos": { body: {}; reply: InfosReply };
+  "/log": { body: LogBody; reply: {} };
+  "/open": { body: JsonValue; reply: Never };
+  "/paths": { body: PathsBody; reply: PathsReply };
+  "/run": { body: RunBody; reply: RunReply };
+  "/show-in-explorer": { body: JsonValue; reply: Never };
+  "/window/close-response": { body: CloseResponseBody; reply: {} };
+  "/window/maximize": { body: {}; reply: {} };
+  "/window/minimize": { body: {}; reply: {} };
+  "/window/request-attention": { body: {}; reply: {} };
+  "/window/restore": { body: {}; reply: {} };
+  "/window/set-always-on-top": { body: ValueBody<boolean | null>; reply: {} };
+  "/window/set-fullscreen": { body: ValueBody<FullscreenMode>; reply: {} };
+  "/window/set-height": { body: ValueBody<number>; reply: {} };
+  "/window/set-ignore-mouse-events": { body: IgnoreMouseEventsBody; reply: {} };
+  "/window/set-maximum-size": { body: Size; reply: {} };
+  "/window/set-minimum-size": { body: Size; reply: {} };
+  "/window/set-resizable": { body: ValueBody<boolean | null>; reply: {} };
+  "/window/set-title": { body: ValueBody<string>; reply: {} };
+  "/window/set-width": { body: ValueBody<number>; reply: {} };
+  "/window/set-x": { body: ValueBody<number>; reply: {} };
+  "/window/set-y": { body: ValueBody<number>; reply: {} };
+  "/window/show-dev-tools": { body: ValueBody<boolean | null>; reply: {} };
+  "/window/state": { body: {}; reply: WindowState };
+  "/window/unmaximize": { body: {}; reply: {} };
+}
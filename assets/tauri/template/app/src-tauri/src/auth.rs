@@ -0,0 +1,66 @@
+use anyhow::Context;
+use std::fmt::Write;
+
+/// URL of the message a client sends first when it didn't pass the token in the handshake
+pub const AUTH_URL: &str = "/auth";
+
+/// Secret generated at each launch and shared only with the game's webview.
+///
+/// Clients present it either in the handshake, as `ws://127.0.0.1:{port}/?token=...`,
+/// or in their first message, `{ url: "/auth", body: { token } }`. Other processes
+/// and web pages can reach the port but can't read the token, so they are rejected.
+pub struct AuthToken(String);
+
+impl AuthToken {
+    /// Generates a token from 32 random bytes, hex encoded
+    pub fn generate() -> anyhow::Result<Self> {
+        Ok(Self(
+            random_hex(32).context("Could not generate the auth token")?,
+        ))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Compares in constant time, so the token can't be guessed byte by byte
+    pub fn verify(&self, candidate: &str) -> bool {
+        let expected = self.0.as_bytes();
+        let candidate = candidate.as_bytes();
+        expected.len() == candidate.len()
+            && expected
+                .iter()
+                .zip(candidate)
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Reads the `token` parameter of a handshake query string
+pub fn token_from_query(query: Option<&str>) -> Option<&str> {
+    query_param(query, "token")
+}
+
+/// Reads the `session` parameter of a handshake query string, see
+/// [`crate::protocol::SessionEvent`]
+pub fn session_from_query(query: Option<&str>) -> Option<&str> {
+    query_param(query, "session")
+}
+
+fn query_param<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?.split('&').find_map(|pair| {
+        pair.strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+    })
+}
+
+/// `len` random bytes, hex encoded
+pub fn random_hex(len: usize) -> anyhow::Result<String> {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Could not generate random bytes: {}", e))?;
+    Ok(bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    }))
+}
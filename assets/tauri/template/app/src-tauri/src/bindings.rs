@@ -0,0 +1,158 @@
+use schemars::{generate::SchemaSettings, JsonSchema, SchemaGenerator};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use tauri::{Runtime, Wry};
+use ts_rs::{TypeVisitor, TS};
+
+use crate::{
+    handlers,
+    protocol::{
+        ErrorBody, EventMessage, IncomingMessage, Progress, ResponseMessage, SessionEvent,
+        StreamMessage, SuccessBody,
+    },
+    router::Router,
+};
+
+/// Header of the generated files
+const GENERATED: &str =
+    "Generated from the Rust message types by `cargo test --test bindings`, do not edit";
+
+/// Protocol definitions for the game plugin, generated from the Rust types
+/// so the runtime and the plugin can't drift apart unnoticed.
+///
+/// The output is checked in under `bindings/`; `tests/bindings.rs` fails when it
+/// no longer matches these types.
+pub struct Bindings {
+    declared: HashSet<String>,
+    declarations: Vec<String>,
+    routes: Vec<String>,
+    schemas: SchemaGenerator,
+    route_schemas: Map<String, Value>,
+}
+
+impl Bindings {
+    /// Collects the protocol messages and every built-in route
+    pub fn new() -> Self {
+        Self::from_router(&handlers::register(Router::<Wry>::new()))
+    }
+
+    /// Collects the protocol messages and the routes registered on `router`,
+    /// with the body and reply types of their handlers
+    pub fn from_router<R: Runtime>(router: &Router<R>) -> Self {
+        let mut bindings = Self {
+            declared: HashSet::new(),
+            declarations: Vec::new(),
+            routes: Vec::new(),
+            schemas: SchemaSettings::draft2020_12().into_generator(),
+            route_schemas: Map::new(),
+        };
+        bindings.protocol();
+        router.declare(&mut bindings);
+        bindings
+    }
+
+    fn protocol(&mut self) {
+        self.declare::<IncomingMessage>();
+        self.declare::<ResponseMessage<Value>>();
+        self.declare::<SuccessBody<Value>>();
+        self.declare::<ErrorBody>();
+        self.declare::<EventMessage<Value>>();
+        self.declare::<StreamMessage<Value>>();
+        self.declare::<Progress>();
+        self.declare::<SessionEvent>();
+    }
+
+    /// Declares a route's body and reply. `()` stands for no body or an empty reply.
+    pub fn route<B, T>(&mut self, url: &str)
+    where
+        B: TS + JsonSchema + 'static,
+        T: TS + JsonSchema + 'static,
+    {
+        self.declare::<B>();
+        self.declare::<T>();
+        self.routes.push(format!(
+            "  {:?}: {{ body: {}; reply: {} }};",
+            url,
+            ts_name::<B>(),
+            ts_name::<T>()
+        ));
+        let body = self.schemas.subschema_for::<B>();
+        let reply = self.schemas.subschema_for::<T>();
+        self.route_schemas
+            .insert(url.to_string(), json!({ "body": body, "reply": reply }));
+    }
+
+    /// URLs of the declared routes
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.route_schemas.keys().map(String::as_str)
+    }
+
+    fn declare<T: TS + JsonSchema + 'static>(&mut self) {
+        self.visit::<T>();
+        let _ = self.schemas.subschema_for::<T>();
+        // Also pulls in the type arguments, e.g. `FullscreenMode` in `ValueBody<FullscreenMode>`
+        T::visit_generics(self);
+    }
+
+    /// TypeScript declarations of every type, then `Routes`, mapping each URL
+    /// to its body and reply
+    pub fn typescript(&self) -> String {
+        let mut output = format!("// {}\n\n", GENERATED);
+        for declaration in &self.declarations {
+            output.push_str(declaration);
+            output.push_str("\n\n");
+        }
+        output.push_str(
+            "/** Body and reply of each route. Replies are flattened into `SuccessBody`. */\n",
+        );
+        output.push_str("export interface Routes {\n");
+        for route in &self.routes {
+            output.push_str(route);
+            output.push('\n');
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    /// JSON Schema with every type under `$defs`, and `routes` mapping each URL
+    /// to the schemas of its body and reply
+    pub fn json_schema(&self) -> Value {
+        let mut schemas = self.schemas.clone();
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "Pipelab protocol",
+            "description": GENERATED,
+            "$defs": schemas.take_definitions(true),
+            "routes": self.route_schemas,
+        })
+    }
+}
+
+/// Name of a type in `Routes`, where `()` (no body or an empty reply) is `{}`
+fn ts_name<T: TS>() -> String {
+    match T::name().as_str() {
+        "null" => "{}".to_string(),
+        name => name.to_string(),
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeVisitor for Bindings {
+    /// Declares a type after the types it depends on, once
+    fn visit<T: TS + 'static + ?Sized>(&mut self) {
+        if T::output_path().is_none() || !self.declared.insert(T::ident()) {
+            return;
+        }
+        T::visit_dependencies(self);
+        self.declarations.push(format!(
+            "{}export {}",
+            T::docs().unwrap_or_default(),
+            T::decl()
+        ));
+    }
+}
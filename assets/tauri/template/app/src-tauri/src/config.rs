@@ -0,0 +1,209 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use ts_rs::TS;
+
+/// Runtime options, read from the `plugins.pipelab` section of `tauri.conf.json`.
+///
+/// Every field has a default, so the section (or any key in it) can be left out.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PipelabConfig {
+    pub server: ServerConfig,
+    pub close_request: CloseRequestConfig,
+    pub connection: ConnectionConfig,
+    pub session: SessionConfig,
+    /// Origins allowed to connect besides the app itself, e.g. `http://localhost:8080`
+    pub allowed_origins: Vec<String>,
+    /// Reject request bodies with fields the route doesn't know, see [`crate::router::parse_body`]
+    pub strict_bodies: bool,
+    pub log: LogConfig,
+    pub shutdown: ShutdownConfig,
+}
+
+/// Where the WebSocket server listens, always on 127.0.0.1.
+/// The port in use is exposed to the game as `window.__PIPELAB__.port`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ServerConfig {
+    /// Preferred port, or 0 to let the OS pick one
+    pub port: u16,
+    /// How many following ports to try when it is taken, before letting the OS pick one
+    pub fallback_ports: u16,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 31753,
+            fallback_ports: 10,
+        }
+    }
+}
+
+/// What happens when the player closes the window while a game is connected
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CloseRequestConfig {
+    /// Ask the game through `/window/close-requested` before closing
+    pub enabled: bool,
+    /// How long to wait for `/window/close-response`
+    pub timeout_ms: u64,
+    /// What to do when the game doesn't answer in time
+    pub on_timeout: CloseTimeoutAction,
+}
+
+impl Default for CloseRequestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_ms: 5000,
+            on_timeout: CloseTimeoutAction::Close,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CloseTimeoutAction {
+    /// Close anyway, so a frozen game can't trap the player
+    Close,
+    /// Keep the window open
+    KeepOpen,
+}
+
+/// Limits of each WebSocket connection
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConnectionConfig {
+    /// Messages waiting to be written to one client
+    pub queue_size: usize,
+    /// What to do with events for a client whose queue is full
+    pub slow_client: SlowClientPolicy,
+    /// How long a client that didn't send the token in the handshake has to send `/auth`
+    pub auth_timeout_ms: u64,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            queue_size: 256,
+            slow_client: SlowClientPolicy::Coalesce,
+            auth_timeout_ms: 10000,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SlowClientPolicy {
+    /// Close the connection
+    Disconnect,
+    /// Hold events until the client catches up, keeping only the latest one per URL
+    Coalesce,
+}
+
+/// How the app exits, see [`crate::shutdown::Shutdown`]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ShutdownConfig {
+    /// How long requests in flight have to finish before clients are disconnected,
+    /// and then how long each shutdown hook has
+    pub timeout_ms: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self { timeout_ms: 3000 }
+    }
+}
+
+/// How long a client that lost its connection keeps its session, see [`crate::connections`]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionConfig {
+    /// How long a client has to reconnect with its session ID, or 0 to never resume
+    pub grace_period_ms: u64,
+    /// Events kept for a client while it is away; older ones are dropped
+    pub max_missed_events: usize,
+    /// Replies kept for a client while it is away; older ones are dropped
+    pub max_missed_replies: usize,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            grace_period_ms: 10000,
+            max_missed_events: 256,
+            max_missed_replies: 256,
+        }
+    }
+}
+
+/// What the runtime logs, to stderr and to `pipelab.log` in the app log folder
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LogConfig {
+    pub level: LogLevel,
+    /// Log down to `debug`, incoming messages included, like Pipelab's `enableExtraLogging`
+    pub enable_extra_logging: bool,
+    /// Size in bytes past which the log file is rotated
+    pub max_file_size: u64,
+    /// Rotated files kept besides the current one
+    pub max_files: usize,
+    /// Longest message body written to the log, in bytes; longer ones are cut
+    pub max_payload_length: usize,
+    /// Body fields whose values never reach the log, e.g. file contents
+    pub redact_fields: Vec<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::Info,
+            enable_extra_logging: false,
+            max_file_size: 5 * 1024 * 1024,
+            max_files: 3,
+            max_payload_length: 1024,
+            redact_fields: ["token", "contents", "content", "base64Data"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl PipelabConfig {
+    /// Reads the `plugins.pipelab` section, falling back to defaults if it is invalid
+    pub fn load(config: &tauri::Config) -> Self {
+        match config.plugins.0.get("pipelab") {
+            Some(value) => serde_json::from_value(value.clone()).unwrap_or_else(|e| {
+                tracing::warn!("Invalid plugins.pipelab config, using defaults: {}", e);
+                Self::default()
+            }),
+            None => Self::default(),
+        }
+    }
+}
+
+impl LogConfig {
+    /// Reads `plugins.pipelab.log` alone, so logging can start before the rest
+    /// of the config is read and its errors reported
+    pub fn load(config: &tauri::Config) -> Self {
+        config
+            .plugins
+            .0
+            .get("pipelab")
+            .and_then(|pipelab| pipelab.get("log"))
+            .and_then(|log| serde_json::from_value(log.clone()).ok())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,532 @@
+use crate::{
+    auth::random_hex,
+    config::SessionConfig,
+    outbox::Outbox,
+    protocol::{EventMessage, SessionEvent},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+use tokio_tungstenite::tungstenite::{protocol::frame::coding::CloseCode, Message};
+use tracing::{debug, info, warn};
+
+/// Identifies the session of one WebSocket client for the lifetime of the process.
+/// It outlives a connection when the client resumes the session from a new one.
+pub type ConnectionId = u64;
+
+/// What the registry keeps about a client, for as long as its session lasts
+struct Client {
+    addr: SocketAddr,
+    session_id: String,
+    /// Queue of the current connection, `None` once the client went away
+    outbox: Option<Outbox>,
+    /// Set until the messages the client missed are queued on its new connection,
+    /// so nothing newer overtakes them
+    replaying: bool,
+    topics: HashSet<String>,
+    /// Requests being handled, by correlation ID. Dropping a sender cancels its request.
+    in_flight: HashMap<String, oneshot::Sender<()>>,
+    /// Messages kept while the client is away, oldest first
+    missed: VecDeque<Missed>,
+    /// Events dropped from `missed` since the client last came back
+    dropped: usize,
+    /// Correlation IDs of the replies dropped from `missed` since then
+    dropped_replies: Vec<String>,
+    away_since: Option<Instant>,
+}
+
+struct Missed {
+    message: Message,
+    kind: Kept,
+}
+
+/// What a kept message is, to know which to drop first
+enum Kept {
+    /// Dropped past `session.maxMissedEvents`
+    Event,
+    /// The reply to the request with this correlation ID, dropped past
+    /// `session.maxMissedReplies`
+    Reply(Option<String>),
+    /// Queued on the connection before it closed. Always kept, there are at most
+    /// `connection.queueSize` of them.
+    Unsent,
+}
+
+impl Kept {
+    fn same_as(&self, other: &Kept) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+impl Client {
+    /// The queue to send to right away, if the client is here and caught up
+    fn live_outbox(&self) -> Option<&Outbox> {
+        self.outbox
+            .as_ref()
+            .filter(|outbox| !self.replaying && !outbox.is_closed())
+    }
+
+    fn is_current(&self, outbox: &Outbox) -> bool {
+        self.outbox
+            .as_ref()
+            .is_some_and(|current| current.same_as(outbox))
+    }
+
+    /// Keeps a message until the client comes back. Past `max` messages of its kind,
+    /// the oldest one is dropped, and the client told when it resumes.
+    fn keep(&mut self, message: Message, kind: Kept, max: usize) {
+        let same_kind = |missed: &Missed| missed.kind.same_as(&kind);
+        let kept = self
+            .missed
+            .iter()
+            .filter(|missed| same_kind(missed))
+            .count();
+        let oldest = self.missed.iter().position(same_kind);
+        self.missed.push_back(Missed { message, kind });
+        if kept < max {
+            return;
+        }
+        // With `max` at 0, the new message is the oldest of its kind
+        let oldest = oldest.unwrap_or(self.missed.len() - 1);
+        match self.missed.remove(oldest).map(|missed| missed.kind) {
+            Some(Kept::Reply(correlation_id)) => self.dropped_replies.extend(correlation_id),
+            _ => self.dropped += 1,
+        }
+    }
+}
+
+/// Receives the events pushed to a game talking through Tauri IPC
+type EventSink = Box<dyn Fn(&EventMessage<Value>) + Send + Sync>;
+
+/// Registry of WebSocket clients, used to push server-initiated messages.
+///
+/// Stored in Tauri's managed state, so any subsystem holding an `AppHandle` can push
+/// with e.g. `app_handle.state::<Connections>().broadcast(url, body)`.
+/// Messages are sent as `{ url, body }`, like the Electron runtime's `broadcastMessage`.
+///
+/// Each client gets a session, announced by a [`SessionEvent`]. When its connection
+/// drops, e.g. the page reloads, the session is kept for `session.gracePeriodMs`:
+/// its requests keep running, and their replies and the events for it are kept
+/// until it reconnects with the session ID, up to `session.maxMissedEvents` and
+/// `session.maxMissedReplies`. Streams don't survive a disconnect, their final reply
+/// tells the request failed.
+///
+/// A game using IPC instead of the socket counts as one more client once it sent
+/// a request, and gets every event, see [`crate::ipc`].
+#[derive(Default)]
+pub struct Connections {
+    next_id: AtomicU64,
+    clients: RwLock<HashMap<ConnectionId, Client>>,
+    max_missed_events: usize,
+    max_missed_replies: usize,
+    ipc_sink: RwLock<Option<EventSink>>,
+    ipc_active: AtomicBool,
+}
+
+impl Connections {
+    pub fn new(config: &SessionConfig) -> Self {
+        Self {
+            max_missed_events: config.max_missed_events,
+            max_missed_replies: config.max_missed_replies,
+            ..Self::default()
+        }
+    }
+
+    /// Starts a session for a new client. Messages for it are held until [`Self::replay`].
+    pub fn register(
+        &self,
+        addr: SocketAddr,
+        outbox: Outbox,
+    ) -> anyhow::Result<(ConnectionId, SessionEvent)> {
+        let session_id = random_hex(16)?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.clients.write().unwrap().insert(
+            id,
+            Client {
+                addr,
+                session_id: session_id.clone(),
+                outbox: Some(outbox),
+                replaying: true,
+                topics: HashSet::new(),
+                in_flight: HashMap::new(),
+                missed: VecDeque::new(),
+                dropped: 0,
+                dropped_replies: Vec::new(),
+                away_since: None,
+            },
+        );
+        let session = SessionEvent {
+            session_id,
+            resumed: false,
+            dropped: 0,
+            dropped_replies: Vec::new(),
+        };
+        Ok((id, session))
+    }
+
+    /// Moves a session to the new connection of its client, closing the previous one
+    /// if it wasn't noticed to be gone yet. Messages are held until [`Self::replay`].
+    /// Returns `None` if there is no such session, e.g. it expired.
+    pub fn resume(
+        &self,
+        session_id: &str,
+        addr: SocketAddr,
+        outbox: Outbox,
+    ) -> Option<(ConnectionId, SessionEvent)> {
+        let mut clients = self.clients.write().unwrap();
+        let (id, client) = clients
+            .iter_mut()
+            .find(|(_, client)| client.session_id == session_id)?;
+        if let Some(previous) = client.outbox.replace(outbox) {
+            previous.close();
+        }
+        client.addr = addr;
+        client.replaying = true;
+        client.away_since = None;
+        let session = SessionEvent {
+            session_id: client.session_id.clone(),
+            resumed: true,
+            dropped: std::mem::take(&mut client.dropped),
+            dropped_replies: std::mem::take(&mut client.dropped_replies),
+        };
+        Some((*id, session))
+    }
+
+    /// Queues what a client missed on its connection, then lets new messages through.
+    /// Returns how many messages were replayed.
+    pub async fn replay(&self, id: ConnectionId, outbox: &Outbox) -> usize {
+        let mut replayed = 0;
+        loop {
+            let missed = {
+                let mut clients = self.clients.write().unwrap();
+                let Some(client) = clients
+                    .get_mut(&id)
+                    .filter(|client| client.is_current(outbox))
+                else {
+                    return replayed;
+                };
+                if client.missed.is_empty() {
+                    client.replaying = false;
+                    return replayed;
+                }
+                std::mem::take(&mut client.missed)
+            };
+            for missed in missed {
+                if !outbox.send(missed.message).await {
+                    return replayed;
+                }
+                replayed += 1;
+            }
+        }
+    }
+
+    /// Keeps the session of a client whose connection closed, with the messages the
+    /// connection didn't get to write, until it resumes or [`Self::expire`] ends it
+    pub fn detach(&self, id: ConnectionId, outbox: &Outbox, unsent: Vec<Message>) {
+        let mut clients = self.clients.write().unwrap();
+        let Some(client) = clients.get_mut(&id) else {
+            return;
+        };
+        if !client.is_current(outbox) {
+            // Already resumed from another connection
+            if client.replaying {
+                for message in unsent.into_iter().rev() {
+                    client.missed.push_front(Missed {
+                        message,
+                        kind: Kept::Unsent,
+                    });
+                }
+            } else if !unsent.is_empty() {
+                debug!(
+                    "Dropped {} message(s) of a replaced connection",
+                    unsent.len()
+                );
+            }
+            return;
+        }
+        client.outbox = None;
+        client.replaying = false;
+        client.away_since = Some(Instant::now());
+        // They were queued before anything kept since
+        for message in unsent.into_iter().rev() {
+            client.missed.push_front(Missed {
+                message,
+                kind: Kept::Unsent,
+            });
+        }
+    }
+
+    /// Ends the session of a client away for at least `grace_period`, cancelling its
+    /// requests. Returns whether it did.
+    pub fn expire(&self, id: ConnectionId, grace_period: Duration) -> bool {
+        let mut clients = self.clients.write().unwrap();
+        let expired = clients
+            .get(&id)
+            .and_then(|client| client.away_since)
+            .is_some_and(|since| since.elapsed() >= grace_period);
+        if expired {
+            clients.remove(&id);
+        }
+        expired
+    }
+
+    /// Forgets a client and its session, cancelling its requests
+    pub fn unregister(&self, id: ConnectionId) {
+        self.clients.write().unwrap().remove(&id);
+    }
+
+    /// Closes every connection with a Close frame giving `reason`, once their
+    /// queued messages are written. Returns how many there were.
+    pub fn close_all(&self, reason: &str) -> usize {
+        let clients = self.clients.read().unwrap();
+        clients
+            .values()
+            .filter_map(|client| client.outbox.as_ref())
+            .inspect(|outbox| outbox.close_with(CloseCode::Away, reason))
+            .count()
+    }
+
+    /// Queues the reply to a request of a client, or keeps it until the client comes
+    /// back. Returns `false` if its session is over.
+    pub async fn reply(
+        &self,
+        id: ConnectionId,
+        correlation_id: Option<String>,
+        mut message: Message,
+    ) -> bool {
+        loop {
+            let outbox = {
+                let mut clients = self.clients.write().unwrap();
+                let Some(client) = clients.get_mut(&id) else {
+                    return false;
+                };
+                match client.live_outbox().cloned() {
+                    Some(outbox) => outbox,
+                    None => {
+                        client.keep(
+                            message,
+                            Kept::Reply(correlation_id),
+                            self.max_missed_replies,
+                        );
+                        return true;
+                    }
+                }
+            };
+            match outbox.send_or_return(message).await {
+                Ok(()) => return true,
+                // Closed in the meantime, so it is kept on the next try
+                Err(returned) => message = returned,
+            }
+        }
+    }
+
+    /// Where to send events once a game uses IPC
+    pub fn set_ipc_sink(&self, sink: impl Fn(&EventMessage<Value>) + Send + Sync + 'static) {
+        *self.ipc_sink.write().unwrap() = Some(Box::new(sink));
+    }
+
+    /// Starts sending events to the IPC sink, when a game sends its first request over IPC
+    pub fn activate_ipc(&self) {
+        if !self.ipc_active.swap(true, Ordering::Relaxed) {
+            info!("Game connected through IPC");
+        }
+    }
+
+    fn ipc_active(&self) -> bool {
+        self.ipc_active.load(Ordering::Relaxed)
+    }
+
+    /// Number of connected clients, counting a game using IPC but not the clients away
+    pub fn len(&self) -> usize {
+        let clients = self.clients.read().unwrap();
+        let connected = clients
+            .values()
+            .filter(|client| client.outbox.is_some())
+            .count();
+        connected + usize::from(self.ipc_active())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Address of a connected client
+    pub fn addr(&self, id: ConnectionId) -> Option<SocketAddr> {
+        self.clients
+            .read()
+            .unwrap()
+            .get(&id)
+            .map(|client| client.addr)
+    }
+
+    /// Queue of a connected client, to send it something other than an event
+    pub fn outbox(&self, id: ConnectionId) -> Option<Outbox> {
+        self.clients
+            .read()
+            .unwrap()
+            .get(&id)
+            .and_then(|client| client.outbox.clone())
+    }
+
+    /// Tracks a request so `/cancel` can reach it. The receiver resolves when it is
+    /// cancelled or the client's session ends, so a client that resumes its session
+    /// still gets the reply. Returns `None` if the client is gone or the correlation ID
+    /// is already in use.
+    pub fn track(&self, id: ConnectionId, correlation_id: &str) -> Option<oneshot::Receiver<()>> {
+        let mut clients = self.clients.write().unwrap();
+        let client = clients.get_mut(&id)?;
+        if client.in_flight.contains_key(correlation_id) {
+            return None;
+        }
+        let (sender, receiver) = oneshot::channel();
+        client.in_flight.insert(correlation_id.to_string(), sender);
+        Some(receiver)
+    }
+
+    /// Stops tracking a request once it is answered
+    pub fn untrack(&self, id: ConnectionId, correlation_id: &str) {
+        if let Some(client) = self.clients.write().unwrap().get_mut(&id) {
+            client.in_flight.remove(correlation_id);
+        }
+    }
+
+    /// Cancels a request of a client. Returns `false` if it isn't in flight.
+    pub fn cancel(&self, id: ConnectionId, correlation_id: &str) -> bool {
+        let sender = self
+            .clients
+            .write()
+            .unwrap()
+            .get_mut(&id)
+            .and_then(|client| client.in_flight.remove(correlation_id));
+        match sender {
+            Some(sender) => {
+                let _ = sender.send(());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Subscribes a client to a topic, see [`Self::emit_topic`]
+    pub fn subscribe(&self, id: ConnectionId, topic: impl Into<String>) -> bool {
+        match self.clients.write().unwrap().get_mut(&id) {
+            Some(client) => {
+                client.topics.insert(topic.into());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn unsubscribe(&self, id: ConnectionId, topic: &str) -> bool {
+        match self.clients.write().unwrap().get_mut(&id) {
+            Some(client) => client.topics.remove(topic),
+            None => false,
+        }
+    }
+
+    /// Sends an event to one client, or keeps it while the client is away.
+    /// Returns `false` if it is gone.
+    pub fn emit_to<T: Serialize>(&self, id: ConnectionId, url: &str, body: T) -> bool {
+        let Some(event) = serialize_event(url, body) else {
+            return false;
+        };
+        match self.clients.write().unwrap().get_mut(&id) {
+            Some(client) => self.push_event(client, url, event),
+            None => false,
+        }
+    }
+
+    /// Sends an event to every client, and to the game if it uses IPC.
+    /// Returns how many clients it was queued for.
+    pub fn broadcast<T: Serialize>(&self, url: &str, body: T) -> usize {
+        self.emit_where(url, body, |_| true)
+    }
+
+    /// Sends an event to the clients subscribed to `topic`. A game using IPC gets it
+    /// anyway: its `listen` callback picks the events it wants.
+    pub fn emit_topic<T: Serialize>(&self, topic: &str, url: &str, body: T) -> usize {
+        self.emit_where(url, body, |client| client.topics.contains(topic))
+    }
+
+    fn emit_where<T: Serialize>(
+        &self,
+        url: &str,
+        body: T,
+        filter: impl Fn(&Client) -> bool,
+    ) -> usize {
+        let body = match serde_json::to_value(body) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to serialize event '{}': {}", url, e);
+                return 0;
+            }
+        };
+        let Some(event) = serialize_event(url, &body) else {
+            return 0;
+        };
+        let sent = self
+            .clients
+            .write()
+            .unwrap()
+            .values_mut()
+            .filter(|client| filter(client))
+            .map(|client| self.push_event(client, url, event.clone()))
+            .filter(|queued| *queued)
+            .count();
+        sent + usize::from(self.emit_ipc(url, body))
+    }
+
+    fn push_event(&self, client: &mut Client, url: &str, event: Arc<str>) -> bool {
+        match client.live_outbox() {
+            Some(outbox) => outbox.push_event(url, event),
+            None => {
+                client.keep(
+                    Message::Text(event.to_string()),
+                    Kept::Event,
+                    self.max_missed_events,
+                );
+                true
+            }
+        }
+    }
+
+    fn emit_ipc(&self, url: &str, body: Value) -> bool {
+        if !self.ipc_active() {
+            return false;
+        }
+        match &*self.ipc_sink.read().unwrap() {
+            Some(sink) => {
+                sink(&EventMessage {
+                    url: url.to_string(),
+                    body,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn serialize_event<T: Serialize>(url: &str, body: T) -> Option<Arc<str>> {
+    let message = EventMessage {
+        url: url.to_string(),
+        body,
+    };
+    match serde_json::to_string(&message) {
+        Ok(json) => Some(json.into()),
+        Err(e) => {
+            warn!("Failed to serialize event '{}': {}", url, e);
+            None
+        }
+    }
+}
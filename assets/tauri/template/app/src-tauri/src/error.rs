@@ -0,0 +1,149 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{backtrace::BacktraceStatus, fmt, io::ErrorKind};
+use ts_rs::TS;
+
+use crate::protocol::ErrorBody;
+
+/// Stable reason sent as `code` in every [`ErrorBody`], so games don't have to
+/// match error messages
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    NotFound,
+    PermissionDenied,
+    AlreadyExists,
+    /// The body is missing a field, has a wrong type, or isn't valid JSON
+    InvalidBody,
+    UnsupportedPlatform,
+    NotImplemented,
+    Timeout,
+    /// Aborted by `/cancel`, or because the client left and its session expired
+    Cancelled,
+    SteamUnavailable,
+    /// The client didn't present the auth token
+    Unauthorized,
+    /// The app is exiting and takes no new requests
+    ShuttingDown,
+    /// Anything else
+    Internal,
+}
+
+/// An error with an explicit code, for failures the code can't be guessed from
+#[derive(Debug)]
+pub struct CodedError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub details: Option<Value>,
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+/// Builds an error with an explicit code
+pub fn coded(code: ErrorCode, message: impl Into<String>) -> anyhow::Error {
+    CodedError {
+        code,
+        message: message.into(),
+        details: None,
+    }
+    .into()
+}
+
+impl ErrorCode {
+    /// Finds the code of an error: the first [`CodedError`] in its chain, otherwise
+    /// one mapped from the I/O, serde or Tauri error it wraps
+    pub fn of(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(|cause| {
+                if let Some(coded) = cause.downcast_ref::<CodedError>() {
+                    return Some(coded.code);
+                }
+                if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+                    return Some(Self::from_io(io.kind()));
+                }
+                if cause.is::<serde_json::Error>() {
+                    return Some(Self::InvalidBody);
+                }
+                if cause.is::<tokio::time::error::Elapsed>() {
+                    return Some(Self::Timeout);
+                }
+                cause.downcast_ref::<tauri::Error>().map(Self::from_tauri)
+            })
+            .unwrap_or(Self::Internal)
+    }
+
+    fn from_io(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::NotFound => Self::NotFound,
+            ErrorKind::PermissionDenied => Self::PermissionDenied,
+            ErrorKind::AlreadyExists => Self::AlreadyExists,
+            ErrorKind::TimedOut => Self::Timeout,
+            ErrorKind::InvalidInput => Self::InvalidBody,
+            ErrorKind::Unsupported => Self::UnsupportedPlatform,
+            _ => Self::Internal,
+        }
+    }
+
+    fn from_tauri(error: &tauri::Error) -> Self {
+        match error {
+            tauri::Error::Io(io) => Self::from_io(io.kind()),
+            tauri::Error::WindowNotFound
+            | tauri::Error::WebviewNotFound
+            | tauri::Error::AssetNotFound(_)
+            | tauri::Error::UnknownPath => Self::NotFound,
+            _ => Self::Internal,
+        }
+    }
+}
+
+/// Extra information about an error, when there is some
+fn details_of(error: &anyhow::Error) -> Option<Value> {
+    error.chain().find_map(|cause| {
+        if let Some(coded) = cause.downcast_ref::<CodedError>() {
+            return coded.details.clone();
+        }
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            return Some(json!({
+                "kind": format!("{:?}", io.kind()),
+                "osError": io.raw_os_error(),
+            }));
+        }
+        if let Some(serde) = cause.downcast_ref::<serde_json::Error>() {
+            return Some(json!({ "line": serde.line(), "column": serde.column() }));
+        }
+        None
+    })
+}
+
+impl ErrorBody {
+    pub fn new(code: ErrorCode, error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error: error.into(),
+            code,
+            details: None,
+            backtrace: None,
+        }
+    }
+
+    /// Describes a handler error. Debug builds also send where it came from.
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let backtrace = error.backtrace();
+        Self {
+            success: false,
+            error: format!("{:#}", error),
+            code: ErrorCode::of(error),
+            details: details_of(error),
+            backtrace: (cfg!(debug_assertions) && backtrace.status() == BacktraceStatus::Captured)
+                .then(|| backtrace.to_string()),
+        }
+    }
+}
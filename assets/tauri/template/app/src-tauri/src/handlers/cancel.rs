@@ -0,0 +1,48 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tauri::{Manager, Runtime};
+use tracing::debug;
+use ts_rs::TS;
+
+use crate::{
+    connections::Connections,
+    error::{coded, ErrorCode},
+    router::{Request, Router},
+};
+
+/// Body of `/cancel`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelBody {
+    /// ID of the request to abort
+    pub correlation_id: String,
+}
+
+pub fn register<R: Runtime>(router: Router<R>) -> Router<R> {
+    router.route("/cancel", handle_cancel::<R>)
+}
+
+/// Body: `{ correlationId }`. Aborts a request of the same connection, which then
+/// replies with a `CANCELLED` error.
+async fn handle_cancel<R: Runtime>(request: Request<R>, body: CancelBody) -> anyhow::Result<()> {
+    let correlation_id = &body.correlation_id;
+    debug!("Handling /cancel request for '{}'", correlation_id);
+
+    let id = request
+        .connection_id
+        .ok_or_else(|| anyhow::anyhow!("/cancel needs a WebSocket connection"))?;
+    if !request
+        .app_handle
+        .state::<Connections>()
+        .cancel(id, correlation_id)
+    {
+        return Err(coded(
+            ErrorCode::NotFound,
+            format!(
+                "No request in flight with correlationId '{}'",
+                correlation_id
+            ),
+        ));
+    }
+    Ok(())
+}
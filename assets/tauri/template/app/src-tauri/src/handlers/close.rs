@@ -0,0 +1,119 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::{sync::Mutex, time::Duration};
+use tauri::{CloseRequestApi, Manager, Runtime, Window};
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+use ts_rs::TS;
+
+use crate::{
+    config::{CloseTimeoutAction, PipelabConfig},
+    connections::Connections,
+    error::{coded, ErrorCode},
+    router::{Request, Router},
+};
+
+pub fn register<R: Runtime>(router: Router<R>) -> Router<R> {
+    router.route("/window/close-response", handle_close_response::<R>)
+}
+
+/// Body of `/window/close-response`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+pub struct CloseResponseBody {
+    /// Whether the window may close
+    pub allow: bool,
+}
+
+/// The close request waiting for the game's answer, if any
+#[derive(Default)]
+pub struct PendingClose(Mutex<Option<oneshot::Sender<bool>>>);
+
+/// Holds a close of the main window until the game answers `/window/close-requested`.
+///
+/// The window closes right away when no client is connected, since there is nobody
+/// to ask. Otherwise it is destroyed once the game allows it, or when the configured
+/// timeout expires with `onTimeout: "close"`.
+pub fn on_close_requested<R: Runtime>(window: &Window<R>, api: &CloseRequestApi) {
+    let config = window.state::<PipelabConfig>().close_request.clone();
+    let events = window.state::<Connections>();
+    if !config.enabled || events.is_empty() {
+        return;
+    }
+
+    api.prevent_close();
+
+    let pending = window.state::<PendingClose>();
+    let receiver = {
+        let mut pending = pending.0.lock().unwrap();
+        if pending.as_ref().is_some_and(|sender| !sender.is_closed()) {
+            // Already waiting for an answer, e.g. the player clicked twice
+            return;
+        }
+        let (sender, receiver) = oneshot::channel();
+        *pending = Some(sender);
+        receiver
+    };
+
+    info!("Close requested, asking the game");
+    events.broadcast(
+        "/window/close-requested",
+        json!({ "timeoutMs": config.timeout_ms }),
+    );
+
+    let window = window.clone();
+    tauri::async_runtime::spawn(async move {
+        let allow =
+            match tokio::time::timeout(Duration::from_millis(config.timeout_ms), receiver).await {
+                Ok(Ok(allow)) => allow,
+                // Timed out, or the pending request was dropped
+                _ => {
+                    info!(
+                        "No answer to the close request, applying {:?}",
+                        config.on_timeout
+                    );
+                    config.on_timeout == CloseTimeoutAction::Close
+                }
+            };
+        {
+            let state = window.state::<PendingClose>();
+            let mut pending = state.0.lock().unwrap();
+            // Only clear our own request, which is closed now that its receiver is gone
+            if pending.as_ref().is_some_and(|sender| sender.is_closed()) {
+                pending.take();
+            }
+        }
+
+        if allow {
+            if let Err(e) = window.destroy() {
+                warn!("Failed to close the window: {}", e);
+            }
+        } else {
+            info!("Close request denied by the game");
+        }
+    });
+}
+
+/// Body: `{ allow }`, the game's answer to `/window/close-requested`
+async fn handle_close_response<R: Runtime>(
+    request: Request<R>,
+    body: CloseResponseBody,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/close-response request");
+
+    let sender = request
+        .app_handle
+        .state::<PendingClose>()
+        .0
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(no_pending_close)?;
+
+    // The request may have timed out in the meantime
+    sender.send(body.allow).map_err(|_| no_pending_close())
+}
+
+fn no_pending_close() -> anyhow::Error {
+    coded(ErrorCode::NotFound, "No close request is pending")
+}
@@ -0,0 +1,55 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tauri::{Manager, Runtime};
+use tracing::debug;
+use ts_rs::TS;
+
+use crate::{
+    connections::Connections,
+    router::{Request, Router},
+};
+
+pub fn register<R: Runtime>(router: Router<R>) -> Router<R> {
+    router
+        .route("/events/subscribe", handle_events_subscribe::<R>)
+        .route("/events/unsubscribe", handle_events_unsubscribe::<R>)
+}
+
+/// Body of `/events/subscribe` and `/events/unsubscribe`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+pub struct TopicBody {
+    pub topic: String,
+}
+
+/// Body: `{ topic }`. The client then receives events emitted on that topic.
+/// Over IPC there is nothing to do, the game already gets every event.
+async fn handle_events_subscribe<R: Runtime>(
+    request: Request<R>,
+    body: TopicBody,
+) -> anyhow::Result<()> {
+    let Some(id) = request.connection_id else {
+        return Ok(());
+    };
+    debug!("Connection {} subscribed to '{}'", id, body.topic);
+    request
+        .app_handle
+        .state::<Connections>()
+        .subscribe(id, &body.topic);
+    Ok(())
+}
+
+/// Body: `{ topic }`
+async fn handle_events_unsubscribe<R: Runtime>(
+    request: Request<R>,
+    body: TopicBody,
+) -> anyhow::Result<()> {
+    let Some(id) = request.connection_id else {
+        return Ok(());
+    };
+    debug!("Connection {} unsubscribed from '{}'", id, body.topic);
+    request
+        .app_handle
+        .state::<Connections>()
+        .unsubscribe(id, &body.topic);
+    Ok(())
+}
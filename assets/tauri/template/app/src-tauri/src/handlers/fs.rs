@@ -0,0 +1,784 @@
+use base64::{
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
+    Engine,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tauri::Runtime;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::watch,
+};
+use tracing::debug;
+use ts_rs::TS;
+
+use crate::{
+    error::{coded, ErrorCode},
+    router::{missing_field, Binary, Declared, IntoReply, Request, Router},
+    stream::ResponseStream,
+};
+
+/// Chunk size of streamed reads when the request doesn't set `chunkSize`
+const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Largest `chunkSize` accepted, so a client can't make the runtime allocate any size
+const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+pub fn register<R: Runtime>(router: Router<R>) -> Router<R> {
+    router
+        .route("/fs/file/write", handle_fs_write::<R>)
+        .route("/fs/file/write-base64", handle_fs_write_base64::<R>)
+        .route("/fs/file/read", handle_fs_read::<R>)
+        .route("/fs/file/read/binary", handle_fs_read_binary::<R>)
+        .route("/fs/folder/create", handle_fs_folder_create::<R>)
+        .route("/fs/copy", handle_fs_copy::<R>)
+        .route("/fs/delete", handle_fs_delete::<R>)
+        .route("/fs/exist", handle_fs_exist::<R>)
+        .route("/fs/list", handle_fs_list::<R>)
+        .route("/fs/file/size", handle_fs_file_size::<R>)
+        .route("/fs/move", handle_fs_move::<R>)
+}
+
+/// One entry of a `/fs/list` reply, as built by the Electron `fs/list` handler
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct ListEntry {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub name: String,
+    pub parent: String,
+    pub path: String,
+}
+
+/// Body of `/fs/file/write`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+pub struct WriteFileBody {
+    pub path: PathBuf,
+    /// Not needed when the data is the payload of a binary frame
+    #[serde(alias = "content")]
+    pub contents: Option<String>,
+    /// Node encoding name, `utf8` by default
+    pub encoding: Option<String>,
+    /// Node file flag; `a` and `a+` append
+    pub flag: Option<String>,
+    pub append: Option<bool>,
+}
+
+/// Body of `/fs/file/write-base64`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteBase64Body {
+    pub path: PathBuf,
+    pub base64_data: String,
+    pub flag: Option<String>,
+}
+
+/// Body of `/fs/file/read`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadFileBody {
+    pub path: PathBuf,
+    /// Node encoding name, `utf8` by default
+    pub encoding: Option<String>,
+    pub stream: Option<bool>,
+    /// Bytes read per chunk when streamed, at most 16 MiB
+    pub chunk_size: Option<usize>,
+}
+
+/// Body of `/fs/file/read/binary`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadBinaryBody {
+    pub path: PathBuf,
+    pub stream: Option<bool>,
+    /// Bytes read per chunk when streamed, at most 16 MiB
+    pub chunk_size: Option<usize>,
+}
+
+/// Body of `/fs/folder/create`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+pub struct CreateFolderBody {
+    pub path: PathBuf,
+    pub recursive: Option<bool>,
+}
+
+/// Body of `/fs/copy`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+pub struct CopyBody {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// `true` by default
+    pub overwrite: Option<bool>,
+    pub stream: Option<bool>,
+}
+
+/// Body of `/fs/move`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+pub struct MoveBody {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// `true` by default
+    pub overwrite: Option<bool>,
+}
+
+/// Body of the routes that only take a path
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+pub struct PathBody {
+    pub path: PathBuf,
+}
+
+/// Reply of `/fs/file/read`. Streamed reads reply with [`SizeReply`] instead.
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct ReadFileReply {
+    pub content: String,
+}
+
+/// Reply of `/fs/file/read/binary` to JSON clients. Binary clients get the bytes
+/// as the payload of a binary frame instead.
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct ReadBinaryReply {
+    pub content: Vec<u8>,
+}
+
+/// Reply of `/fs/file/size`, and of streamed reads
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct SizeReply {
+    #[ts(type = "number")]
+    pub size: u64,
+}
+
+/// Reply of `/fs/exist`
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct ExistReply {
+    pub success: bool,
+}
+
+/// Reply of `/fs/list`
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct ListReply {
+    pub list: Vec<ListEntry>,
+}
+
+/// Writes a file the way the Electron `fs/write` handler does.
+///
+/// Body: `{ path, contents, encoding?, flag?, append? }`. `content` is accepted as an
+/// alias of `contents`, and `flag: "a"` is the same as `append: true`.
+/// In a binary frame, the payload is written as is and `contents` is not needed.
+/// Missing parent folders are created. Unless appending, the data goes to a temporary
+/// file that is synced and then renamed over the target, so a crash mid-write leaves
+/// either the old file or the new one, never a truncated mix of both.
+async fn handle_fs_write<R: Runtime>(
+    mut request: Request<R>,
+    body: WriteFileBody,
+) -> anyhow::Result<()> {
+    debug!("Handling /fs/file/write request.");
+
+    let path = body.path;
+    let append = body.append.unwrap_or(false) || is_append_flag(body.flag.as_deref());
+
+    let data = match request.payload.take() {
+        Some(payload) => payload,
+        None => {
+            let contents = body.contents.ok_or_else(|| missing_field("contents"))?;
+            let encoding = body.encoding.as_deref().unwrap_or("utf8");
+            decode_contents(&contents, encoding)?
+        }
+    };
+
+    debug!(
+        "Writing {} bytes to '{}' (append: {})",
+        data.len(),
+        path.display(),
+        append
+    );
+
+    create_parent_dir(&path).await?;
+    if append {
+        append_file(&path, &data).await
+    } else {
+        write_file_atomic(&path, &data).await
+    }
+}
+
+/// Body: `{ path, base64Data, flag? }`
+async fn handle_fs_write_base64<R: Runtime>(
+    _request: Request<R>,
+    body: WriteBase64Body,
+) -> anyhow::Result<()> {
+    debug!("Handling /fs/file/write-base64 request.");
+
+    let data = decode_contents(&body.base64_data, "base64")?;
+
+    create_parent_dir(&body.path).await?;
+    if is_append_flag(body.flag.as_deref()) {
+        append_file(&body.path, &data).await
+    } else {
+        write_file_atomic(&body.path, &data).await
+    }
+}
+
+fn is_append_flag(flag: Option<&str>) -> bool {
+    flag.is_some_and(|flag| flag.starts_with('a'))
+}
+
+/// Body: `{ path, encoding?, stream?, chunkSize? }`, replies `{ content }` (utf8 unless
+/// told otherwise). When streamed, the content comes in chunks and the reply is `{ size }`.
+async fn handle_fs_read<R: Runtime>(
+    request: Request<R>,
+    body: ReadFileBody,
+) -> anyhow::Result<Declared<ReadFileReply>> {
+    debug!("Handling /fs/file/read request.");
+
+    let encoding = body.encoding.as_deref().unwrap_or("utf8");
+    if let Some(stream) = &request.stream {
+        return Ok(
+            stream_file(&body.path, stream, body.chunk_size, Some(encoding))
+                .await?
+                .into_reply()?
+                .declared(),
+        );
+    }
+    let data = tokio::fs::read(&body.path).await?;
+
+    Ok(ReadFileReply {
+        content: encode_contents(&data, encoding)?,
+    }
+    .into_reply()?
+    .declared())
+}
+
+/// Body: `{ path, stream?, chunkSize? }`, replies `{ content }` as an array of bytes,
+/// or with the raw bytes when asked in a binary frame.
+/// When streamed, the bytes come in chunks and the reply is `{ size }`.
+async fn handle_fs_read_binary<R: Runtime>(
+    request: Request<R>,
+    body: ReadBinaryBody,
+) -> anyhow::Result<Declared<ReadBinaryReply>> {
+    debug!("Handling /fs/file/read/binary request.");
+
+    if let Some(stream) = &request.stream {
+        return Ok(stream_file(&body.path, stream, body.chunk_size, None)
+            .await?
+            .into_reply()?
+            .declared());
+    }
+    let data = tokio::fs::read(&body.path).await?;
+
+    Ok(Binary {
+        meta: (),
+        field: "content",
+        data,
+    }
+    .into_reply()?
+    .declared())
+}
+
+/// Sends a file in chunks of `chunk_size` bytes, encoded as text with `encoding`
+/// or as raw bytes, and replies `{ size }`
+async fn stream_file(
+    path: &Path,
+    stream: &ResponseStream,
+    chunk_size: Option<usize>,
+    encoding: Option<&str>,
+) -> anyhow::Result<SizeReply> {
+    let chunk_size = chunk_size.map_or(DEFAULT_CHUNK_SIZE, |size| size.max(1));
+    if chunk_size > MAX_CHUNK_SIZE {
+        return Err(coded(
+            ErrorCode::InvalidBody,
+            format!("chunkSize can't be more than {} bytes", MAX_CHUNK_SIZE),
+        ));
+    }
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = Vec::with_capacity(chunk_size);
+    let mut size = 0;
+
+    loop {
+        // The buffer may start with the end of a character cut by the previous read
+        let start = buffer.len();
+        buffer.resize(start + chunk_size, 0);
+        let read = file.read(&mut buffer[start..]).await?;
+        buffer.truncate(start + read);
+        size += read as u64;
+
+        let end = match encoding {
+            Some(encoding) if read > 0 => complete_len(&buffer, encoding),
+            _ => buffer.len(),
+        };
+        if end > 0 {
+            match encoding {
+                Some(encoding) => {
+                    stream
+                        .chunk(encode_contents(&buffer[..end], encoding)?)
+                        .await?
+                }
+                None => stream.bytes(&buffer[..end]).await?,
+            }
+        }
+        buffer.drain(..end);
+        if read == 0 {
+            break;
+        }
+    }
+
+    Ok(SizeReply { size })
+}
+
+/// Body: `{ path, recursive? }`
+async fn handle_fs_folder_create<R: Runtime>(
+    _request: Request<R>,
+    body: CreateFolderBody,
+) -> anyhow::Result<()> {
+    debug!("Handling /fs/folder/create request.");
+
+    if body.recursive.unwrap_or(false) {
+        tokio::fs::create_dir_all(&body.path).await?;
+    } else {
+        tokio::fs::create_dir(&body.path).await?;
+    }
+    Ok(())
+}
+
+/// Body: `{ source, destination, overwrite?, stream? }`. Folders are copied recursively;
+/// existing files are kept when `overwrite` is `false`.
+/// When streamed, progress is reported in bytes. Cancelling the request stops the
+/// copy after the current file.
+async fn handle_fs_copy<R: Runtime>(request: Request<R>, body: CopyBody) -> anyhow::Result<()> {
+    debug!("Handling /fs/copy request.");
+
+    let CopyBody {
+        source,
+        destination,
+        overwrite,
+        ..
+    } = body;
+    let overwrite = overwrite.unwrap_or(true);
+
+    check_destination("copy", &source, &destination)?;
+    create_parent_dir(&destination).await?;
+    let total = match &request.stream {
+        Some(_) => {
+            let source = source.clone();
+            tokio::task::spawn_blocking(move || total_size(&source)).await??
+        }
+        None => 0,
+    };
+    let (copied, mut progress) = watch::channel(0);
+    let copy = tokio::task::spawn_blocking(move || {
+        let mut done = 0;
+        copy_recursive(&source, &destination, overwrite, &mut |bytes| {
+            done += bytes;
+            copied.send_replace(done);
+            // Nobody listens anymore once the request is cancelled
+            !copied.is_closed()
+        })
+    });
+    // Only the latest count is sent when the client is slower than the copy.
+    // Ends once the copy is over and drops `copied`.
+    while progress.changed().await.is_ok() {
+        if let Some(stream) = &request.stream {
+            let done = *progress.borrow_and_update();
+            stream.progress(done, Some(total)).await?;
+        }
+    }
+    copy.await?
+}
+
+/// Body: `{ path }`. Folders are deleted with their content.
+async fn handle_fs_delete<R: Runtime>(_request: Request<R>, body: PathBody) -> anyhow::Result<()> {
+    debug!("Handling /fs/delete request.");
+
+    if tokio::fs::symlink_metadata(&body.path).await?.is_dir() {
+        tokio::fs::remove_dir_all(&body.path).await?;
+    } else {
+        tokio::fs::remove_file(&body.path).await?;
+    }
+    Ok(())
+}
+
+/// Body: `{ path }`. Like the Electron handler, a missing path is reported as
+/// `success: false` without an error message.
+async fn handle_fs_exist<R: Runtime>(
+    _request: Request<R>,
+    body: PathBody,
+) -> anyhow::Result<ExistReply> {
+    debug!("Handling /fs/exist request.");
+
+    let exists = tokio::fs::try_exists(&body.path).await.unwrap_or(false);
+
+    Ok(ExistReply { success: exists })
+}
+
+/// Body: `{ path }`, replies `{ list }` with every file and folder below `path`
+async fn handle_fs_list<R: Runtime>(
+    _request: Request<R>,
+    body: PathBody,
+) -> anyhow::Result<ListReply> {
+    debug!("Handling /fs/list request.");
+
+    let path = body.path;
+    let list = tokio::task::spawn_blocking(move || {
+        let parent = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut list = Vec::new();
+        list_recursive(&path, &parent, &mut list)?;
+        anyhow::Ok(list)
+    })
+    .await??;
+
+    Ok(ListReply { list })
+}
+
+/// Body: `{ path }`, replies `{ size }` in bytes
+async fn handle_fs_file_size<R: Runtime>(
+    _request: Request<R>,
+    body: PathBody,
+) -> anyhow::Result<SizeReply> {
+    debug!("Handling /fs/file/size request.");
+
+    let metadata = tokio::fs::metadata(&body.path).await?;
+
+    Ok(SizeReply {
+        size: metadata.len(),
+    })
+}
+
+/// Body: `{ source, destination, overwrite? }`. Falls back to copy + delete when
+/// moving across drives.
+async fn handle_fs_move<R: Runtime>(_request: Request<R>, body: MoveBody) -> anyhow::Result<()> {
+    debug!("Handling /fs/move request.");
+
+    let MoveBody {
+        source,
+        destination,
+        overwrite,
+    } = body;
+
+    if !overwrite.unwrap_or(true) && tokio::fs::try_exists(&destination).await? {
+        return Err(coded(
+            ErrorCode::AlreadyExists,
+            format!("The destination file exists: {}", destination.display()),
+        ));
+    }
+
+    check_destination("move", &source, &destination)?;
+    create_parent_dir(&destination).await?;
+    match tokio::fs::rename(&source, &destination).await {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        result => return Ok(result?),
+    }
+    tokio::task::spawn_blocking(move || {
+        let existed = destination.exists();
+        if let Err(e) = copy_recursive(&source, &destination, true, &mut |_| true) {
+            // Don't leave half a copy behind, unless it went over something
+            if !existed {
+                let _ = remove_path(&destination);
+            }
+            return Err(e);
+        }
+        remove_path(&source)?;
+        anyhow::Ok(())
+    })
+    .await?
+}
+
+// --- Helpers ---
+
+/// Turns the string sent by the client into bytes, using Node's encoding names
+pub fn decode_contents(contents: &str, encoding: &str) -> anyhow::Result<Vec<u8>> {
+    match encoding.to_ascii_lowercase().as_str() {
+        "utf8" | "utf-8" => Ok(contents.as_bytes().to_vec()),
+        "base64" => Ok(BASE64_STANDARD.decode(contents.trim())?),
+        "base64url" => Ok(BASE64_URL_SAFE_NO_PAD.decode(contents.trim().trim_end_matches('='))?),
+        "hex" => decode_hex(contents.trim()),
+        // Node keeps the low byte of each code unit for these
+        "latin1" | "binary" | "ascii" => Ok(contents.chars().map(|c| c as u32 as u8).collect()),
+        "utf16le" | "utf-16le" | "ucs2" | "ucs-2" => Ok(contents
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect()),
+        other => Err(unsupported_encoding(other)),
+    }
+}
+
+fn unsupported_encoding(encoding: &str) -> anyhow::Error {
+    coded(
+        ErrorCode::InvalidBody,
+        format!("Unsupported encoding: {}", encoding),
+    )
+}
+
+fn decode_hex(contents: &str) -> anyhow::Result<Vec<u8>> {
+    if !contents.len().is_multiple_of(2) {
+        return Err(coded(
+            ErrorCode::InvalidBody,
+            "Invalid hex string: odd length",
+        ));
+    }
+    (0..contents.len())
+        .step_by(2)
+        .map(|i| {
+            contents
+                .get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| {
+                    coded(
+                        ErrorCode::InvalidBody,
+                        format!("Invalid hex string at offset {}", i),
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Turns file bytes into the string sent back to the client, the reverse of [`decode_contents`]
+pub fn encode_contents(data: &[u8], encoding: &str) -> anyhow::Result<String> {
+    match encoding.to_ascii_lowercase().as_str() {
+        "utf8" | "utf-8" => Ok(String::from_utf8_lossy(data).into_owned()),
+        "base64" => Ok(BASE64_STANDARD.encode(data)),
+        "base64url" => Ok(BASE64_URL_SAFE_NO_PAD.encode(data)),
+        "hex" => Ok(data.iter().map(|byte| format!("{:02x}", byte)).collect()),
+        "latin1" | "binary" => Ok(data.iter().map(|&byte| byte as char).collect()),
+        "ascii" => Ok(data.iter().map(|&byte| (byte & 0x7f) as char).collect()),
+        "utf16le" | "utf-16le" | "ucs2" | "ucs-2" => {
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            Ok(String::from_utf16_lossy(&units))
+        }
+        other => Err(unsupported_encoding(other)),
+    }
+}
+
+/// Copies a file, or a folder and everything in it.
+/// `on_file` gets the size of each file once it is done, copied or kept,
+/// and stops the copy by returning `false`.
+pub fn copy_recursive(
+    source: &Path,
+    destination: &Path,
+    overwrite: bool,
+    on_file: &mut dyn FnMut(u64) -> bool,
+) -> anyhow::Result<()> {
+    let metadata = std::fs::metadata(source)?;
+    if metadata.is_dir() {
+        std::fs::create_dir_all(destination)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(
+                &entry.path(),
+                &destination.join(entry.file_name()),
+                overwrite,
+                on_file,
+            )?;
+        }
+    } else {
+        if overwrite || !destination.exists() {
+            std::fs::copy(source, destination)?;
+        }
+        if !on_file(metadata.len()) {
+            return Err(anyhow::anyhow!("Copy stopped"));
+        }
+    }
+    Ok(())
+}
+
+/// Refuses to copy or move `source` onto itself or into its own subfolder, like
+/// Node's `fs.cp`, which would otherwise copy the copy forever
+fn check_destination(action: &str, source: &Path, destination: &Path) -> anyhow::Result<()> {
+    let source_path = std::fs::canonicalize(source)?;
+    let destination_path = resolve(destination)?;
+    if destination_path == source_path {
+        return Err(coded(
+            ErrorCode::InvalidBody,
+            format!(
+                "Source and destination cannot be the same: {}",
+                source.display()
+            ),
+        ));
+    }
+    if destination_path.starts_with(&source_path) {
+        return Err(coded(
+            ErrorCode::InvalidBody,
+            format!(
+                "Cannot {} {} to a subdirectory of self {}",
+                action,
+                source.display(),
+                destination.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Deletes a file, or a folder with its content
+fn remove_path(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Canonical form of a path that may not exist yet: its closest existing ancestor
+/// canonicalized, followed by the rest
+fn resolve(path: &Path) -> anyhow::Result<PathBuf> {
+    let path = std::path::absolute(path)?;
+    let mut existing = path.as_path();
+    let mut rest = Vec::new();
+    loop {
+        match std::fs::canonicalize(existing) {
+            Ok(resolved) => {
+                return Ok(rest
+                    .into_iter()
+                    .rev()
+                    .fold(resolved, |path, name| path.join(name)))
+            }
+            Err(_) => match (existing.parent(), existing.file_name()) {
+                (Some(parent), Some(name)) => {
+                    rest.push(name);
+                    existing = parent;
+                }
+                _ => return Ok(path),
+            },
+        }
+    }
+}
+
+/// Size of a file, or of everything in a folder
+pub fn total_size(path: &Path) -> anyhow::Result<u64> {
+    let metadata = std::fs::metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        size += total_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Length of the longest start of `data` that can be encoded on its own, so that
+/// text sent in chunks doesn't have characters or base64 groups cut in two
+pub fn complete_len(data: &[u8], encoding: &str) -> usize {
+    match encoding.to_ascii_lowercase().as_str() {
+        "utf8" | "utf-8" => match std::str::from_utf8(data) {
+            // Only an incomplete character at the end, keep it for the next chunk
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            _ => data.len(),
+        },
+        "base64" | "base64url" => data.len() - data.len() % 3,
+        "utf16le" | "utf-16le" | "ucs2" | "ucs-2" => data.len() - data.len() % 2,
+        _ => data.len(),
+    }
+}
+
+/// Depth-first listing matching the Electron `fs/list` output: entries of `dir`
+/// report `parent` as the folder passed in, nested ones as their own folder
+fn list_recursive(dir: &Path, parent: &Path, list: &mut Vec<ListEntry>) -> anyhow::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let full_path = dir.join(entry.file_name());
+        let is_dir = entry.file_type()?.is_dir();
+        list.push(ListEntry {
+            kind: if is_dir { "directory" } else { "file" },
+            name: entry.file_name().to_string_lossy().into_owned(),
+            parent: slash(parent),
+            path: slash(&full_path),
+        });
+        if is_dir {
+            list_recursive(&full_path, &full_path, list)?;
+        }
+    }
+    Ok(())
+}
+
+/// Converts Windows separators to forward slashes, like the `slash` npm package
+/// (extended-length `\\?\` paths are left untouched)
+pub fn slash(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if path.starts_with(r"\\?\") {
+        path.into_owned()
+    } else {
+        path.replace('\\', "/")
+    }
+}
+
+/// Creates the folder a file will be written to, like `mkdir -p $(dirname path)`
+pub async fn create_parent_dir(path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    Ok(())
+}
+
+/// Appends to a file, creating it if needed, and syncs it to disk
+pub async fn append_file(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+/// Replaces a file atomically: write a sibling temp file, fsync it, rename it over `path`
+pub async fn write_file_atomic(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let temp_path = temp_path_for(path)?;
+
+    let result = async {
+        let mut file = tokio::fs::File::create(&temp_path).await?;
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        drop(file);
+        tokio::fs::rename(&temp_path, path).await?;
+        anyhow::Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(e);
+    }
+
+    // Persist the rename itself; not possible (nor needed) on Windows
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Ok(dir) = tokio::fs::File::open(parent).await {
+            let _ = dir.sync_all().await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Picks a temp file next to `path`, so the final rename never crosses filesystems
+fn temp_path_for(path: &Path) -> anyhow::Result<PathBuf> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid file path: {}", path.display()))?;
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(path.with_file_name(temp_name))
+}
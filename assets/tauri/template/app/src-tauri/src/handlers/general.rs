@@ -0,0 +1,250 @@
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::HashMap, path::PathBuf, process::Stdio};
+use tauri::Runtime;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::{debug, info};
+use ts_rs::TS;
+
+use crate::{
+    error::{coded, ErrorCode},
+    handlers::{fs::complete_len, not_implemented},
+    router::{Request, Router},
+    shutdown,
+    stream::ResponseStream,
+};
+
+/// Body of `/run`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+pub struct RunBody {
+    pub command: String,
+    pub args: Option<Vec<String>>,
+    pub cwd: Option<PathBuf>,
+    /// Added to the runtime's own environment
+    pub env: Option<HashMap<String, String>>,
+    pub stream: Option<bool>,
+}
+
+/// Reply of `/run`. The output is left out when it was streamed.
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct RunReply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub stdout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub stderr: Option<String>,
+    /// `null` when the process was killed by a signal
+    pub code: Option<i32>,
+}
+
+/// Body of `/exit`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+pub struct ExitBody {
+    /// Exit code of the process, 0 by default
+    pub code: Option<i32>,
+}
+
+/// Reply of `/engine`
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct EngineReply {
+    /// Always `"tauri"`; the Electron runtime answers `"electron"`
+    pub engine: &'static str,
+}
+
+/// Reply of `/infos`, with Node's names for the architecture and platform
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct InfosReply {
+    /// `x64`, `arm64`, `ia32`...
+    pub arch: String,
+    /// `win32`, `darwin`, `linux`...
+    pub platform: String,
+    /// Version of the app
+    pub version: String,
+}
+
+/// Routes the Electron runtime answers but this one doesn't yet
+pub const NOT_IMPLEMENTED_ROUTES: &[&str] = &[
+    "/dialog/folder",
+    "/dialog/open",
+    "/dialog/save",
+    "/open",
+    "/show-in-explorer",
+    "/discord/set-activity",
+];
+
+pub fn register<R: Runtime>(router: Router<R>) -> Router<R> {
+    let router = NOT_IMPLEMENTED_ROUTES.iter().fold(router, |router, url| {
+        router.route(*url, not_implemented::<R>)
+    });
+    router
+        .route("/engine", handle_engine::<R>)
+        .route("/infos", handle_infos::<R>)
+        .route("/exit", handle_exit::<R>)
+        .route("/run", handle_run::<R>)
+        .prefix("/steam/", handle_steam::<R>)
+}
+
+async fn handle_engine<R: Runtime>(_request: Request<R>) -> anyhow::Result<EngineReply> {
+    debug!("Handling /engine request");
+    Ok(EngineReply { engine: "tauri" })
+}
+
+/// Replies `{ arch, platform, version }` like the Electron `infos` handler,
+/// which reports Node's `os.arch()` and `os.platform()`
+async fn handle_infos<R: Runtime>(request: Request<R>) -> anyhow::Result<InfosReply> {
+    debug!("Handling /infos request");
+
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "x86" => "ia32",
+        "aarch64" => "arm64",
+        "powerpc64" => "ppc64",
+        other => other,
+    };
+    let platform = match std::env::consts::OS {
+        "windows" => "win32",
+        "macos" => "darwin",
+        other => other,
+    };
+    Ok(InfosReply {
+        arch: arch.to_string(),
+        platform: platform.to_string(),
+        version: request.app_handle.package_info().version.to_string(),
+    })
+}
+
+/// Body: `{ code? }`. Replies, then exits with `code` once the app shut down, see
+/// [`shutdown::Shutdown`].
+async fn handle_exit<R: Runtime>(request: Request<R>, body: ExitBody) -> anyhow::Result<()> {
+    let code = body.code.unwrap_or(0);
+    info!("Handling /exit request with code {}", code);
+
+    // The shutdown waits for this request, so its reply goes out first
+    shutdown::exit(&request.app_handle, code);
+    Ok(())
+}
+
+/// Steam isn't integrated in this runtime yet, so games can tell it apart
+/// from a failed Steam call
+async fn handle_steam<R: Runtime>(request: Request<R>) -> anyhow::Result<()> {
+    debug!("Handling {} request", request.url);
+    Err(coded(
+        ErrorCode::SteamUnavailable,
+        "Steam is not available in this runtime",
+    ))
+}
+
+/// Body: `{ command, args?, cwd?, env?, stream? }`, replies `{ stdout, stderr, code }`
+/// like the Electron `run` handler. A non-zero exit code is an error.
+///
+/// When streamed, output is sent as it comes in chunks of `{ stream, data }`, where
+/// `stream` is `"stdout"` or `"stderr"`, and the reply only has `code`.
+/// The process is killed if the request is dropped.
+async fn handle_run<R: Runtime>(request: Request<R>, body: RunBody) -> anyhow::Result<RunReply> {
+    debug!("Handling /run request");
+
+    let command = &body.command;
+    let mut process = tokio::process::Command::new(command);
+    process
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(args) = &body.args {
+        process.args(args);
+    }
+    if let Some(cwd) = &body.cwd {
+        process.current_dir(cwd);
+    }
+    if let Some(env) = &body.env {
+        process.envs(env);
+    }
+
+    let mut child = process
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", command))?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let stream = request.stream.as_ref();
+    let (stdout, stderr) = tokio::try_join!(
+        read_output(stdout, "stdout", stream),
+        read_output(stderr, "stderr", stream),
+    )?;
+    let status = child.wait().await?;
+
+    if !status.success() {
+        let stderr = stderr.trim();
+        if stderr.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Command '{}' failed with {}",
+                command,
+                status
+            ));
+        }
+        return Err(anyhow::anyhow!(
+            "Command '{}' failed with {}: {}",
+            command,
+            status,
+            stderr
+        ));
+    }
+    if stream.is_some() {
+        return Ok(RunReply {
+            stdout: None,
+            stderr: None,
+            code: status.code(),
+        });
+    }
+    Ok(RunReply {
+        stdout: Some(stdout),
+        stderr: Some(stderr),
+        code: status.code(),
+    })
+}
+
+/// Collects the output of a process, or sends it through `stream` as it comes
+async fn read_output(
+    mut output: impl AsyncRead + Unpin,
+    name: &str,
+    stream: Option<&ResponseStream>,
+) -> anyhow::Result<String> {
+    let mut collected = Vec::new();
+    let mut pending = Vec::new();
+    let mut buffer = [0; 8192];
+
+    loop {
+        let read = output.read(&mut buffer).await?;
+        let Some(stream) = stream else {
+            if read == 0 {
+                break;
+            }
+            collected.extend_from_slice(&buffer[..read]);
+            continue;
+        };
+
+        pending.extend_from_slice(&buffer[..read]);
+        // Keep a character cut in two for the next read
+        let end = if read == 0 {
+            pending.len()
+        } else {
+            complete_len(&pending, "utf8")
+        };
+        if end > 0 {
+            let data = String::from_utf8_lossy(&pending[..end]);
+            stream
+                .chunk(json!({ "stream": name, "data": data }))
+                .await?;
+            pending.drain(..end);
+        }
+        if read == 0 {
+            break;
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&collected).into_owned())
+}
@@ -0,0 +1,46 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tauri::Runtime;
+use tracing::{debug, error, info, trace, warn};
+use ts_rs::TS;
+
+use crate::{
+    config::LogLevel,
+    logging::Payload,
+    router::{Request, Router},
+};
+
+pub fn register<R: Runtime>(router: Router<R>) -> Router<R> {
+    router.route("/log", handle_log::<R>)
+}
+
+/// Body of `/log`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+pub struct LogBody {
+    /// `info` by default
+    pub level: Option<LogLevel>,
+    pub message: String,
+    /// Context written after the message, with the `redactFields` hidden
+    pub fields: Option<Map<String, Value>>,
+}
+
+/// Body: `{ level?, message, fields? }`. Writes a message of the game to the runtime's
+/// log file, under the `game` target, so both can be read side by side.
+async fn handle_log<R: Runtime>(_request: Request<R>, body: LogBody) -> anyhow::Result<()> {
+    let fields = body
+        .fields
+        .map(|fields| Payload(&Value::Object(fields)).to_string());
+    let fields = fields.as_deref();
+    let message = &body.message;
+
+    match body.level.unwrap_or(LogLevel::Info) {
+        LogLevel::Error => error!(target: "game", fields, "{}", message),
+        LogLevel::Warn => warn!(target: "game", fields, "{}", message),
+        LogLevel::Info => info!(target: "game", fields, "{}", message),
+        LogLevel::Debug => debug!(target: "game", fields, "{}", message),
+        LogLevel::Trace => trace!(target: "game", fields, "{}", message),
+    }
+    Ok(())
+}
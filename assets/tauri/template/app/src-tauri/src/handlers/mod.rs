@@ -0,0 +1,49 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::Runtime;
+use tracing::debug;
+use ts_rs::TS;
+
+use crate::{
+    error::{coded, ErrorCode},
+    router::{Request, Router},
+};
+
+pub mod cancel;
+pub mod close;
+pub mod events;
+pub mod fs;
+pub mod general;
+pub mod log;
+pub mod paths;
+pub mod window;
+
+/// Registers every built-in route on the router
+pub fn register<R: Runtime>(router: Router<R>) -> Router<R> {
+    let router = general::register(router);
+    let router = events::register(router);
+    let router = cancel::register(router);
+    let router = fs::register(router);
+    let router = log::register(router);
+    let router = paths::register(router);
+    let router = window::register(router);
+    close::register(router)
+}
+
+/// Reply of routes that always fail, `never` in the bindings
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub enum Never {}
+
+/// Placeholder for routes the Electron runtime supports but this one doesn't yet.
+/// Takes any body, the way the Electron route does.
+pub async fn not_implemented<R: Runtime>(
+    request: Request<R>,
+    _body: Value,
+) -> anyhow::Result<Never> {
+    debug!("Handler not implemented for URL: {}", request.url);
+    Err(coded(
+        ErrorCode::NotImplemented,
+        format!("Feature not implemented: {}", request.url),
+    ))
+}
@@ -0,0 +1,196 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{utils::config::FrontendDist, AppHandle, Manager, Runtime};
+use tracing::debug;
+use ts_rs::TS;
+
+use crate::{
+    error::{coded, ErrorCode},
+    handlers::fs::slash,
+    router::{Request, Router},
+};
+
+pub fn register<R: Runtime>(router: Router<R>) -> Router<R> {
+    router.route("/paths", handle_paths::<R>)
+}
+
+/// Names accepted by `/paths`, mirroring the Electron `user/folder` handler
+pub const PATH_NAMES: &[&str] = &[
+    "app",
+    "project",
+    "home",
+    "appData",
+    "userData",
+    "localAppData",
+    "localUserData",
+    "temp",
+    "exe",
+    "desktop",
+    "documents",
+    "downloads",
+    "music",
+    "pictures",
+    "videos",
+    "logs",
+];
+
+/// Body of `/paths`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+pub struct PathsBody {
+    /// One of [`PATH_NAMES`]
+    pub name: String,
+}
+
+/// Reply of `/paths`
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct PathsReply {
+    /// The folder, using forward slashes
+    pub data: String,
+}
+
+/// Body: `{ name }`, replies `{ data }` with the folder, using forward slashes
+async fn handle_paths<R: Runtime>(
+    request: Request<R>,
+    body: PathsBody,
+) -> anyhow::Result<PathsReply> {
+    debug!("Handling /paths request");
+
+    let folder = resolve_path(&request.app_handle, &body.name)?;
+
+    Ok(PathsReply {
+        data: slash(&folder),
+    })
+}
+
+/// Resolves a folder by its Electron name
+pub fn resolve_path<R: Runtime>(app_handle: &AppHandle<R>, name: &str) -> anyhow::Result<PathBuf> {
+    let path = app_handle.path();
+
+    let folder = match name {
+        "app" => path.resource_dir()?,
+        "project" => project_dir(app_handle)?,
+        "home" => path.home_dir()?,
+        "appData" => app_data_dir()?,
+        "userData" => app_data_dir()?.join(app_name(app_handle)),
+        "localAppData" => local_app_data_dir()?,
+        "localUserData" => local_app_data_dir()?.join(app_name(app_handle)),
+        "temp" => path.temp_dir()?,
+        "exe" => std::env::current_exe()?,
+        "desktop" => path.desktop_dir()?,
+        "documents" => path.document_dir()?,
+        "downloads" => path.download_dir()?,
+        "music" => path.audio_dir()?,
+        "pictures" => path.picture_dir()?,
+        "videos" => path.video_dir()?,
+        "logs" => path.app_log_dir()?,
+        _ => {
+            return Err(coded(
+                ErrorCode::InvalidBody,
+                format!(
+                    "Unknown path name '{}', expected one of: {}",
+                    name,
+                    PATH_NAMES.join(", ")
+                ),
+            ))
+        }
+    };
+    Ok(folder)
+}
+
+/// Roaming data folder: `APPDATA` on Windows, `XDG_CONFIG_HOME` on Linux,
+/// otherwise the OS default (Electron's `app.getPath('appData')`)
+fn app_data_dir() -> anyhow::Result<PathBuf> {
+    env_dir("APPDATA")
+        .or_else(|| env_dir("XDG_CONFIG_HOME"))
+        .or_else(dirs::config_dir)
+        .ok_or_else(|| anyhow::anyhow!("Could not get app data dir"))
+}
+
+/// Local data folder: `LOCALAPPDATA` on Windows, `XDG_DATA_HOME` on Linux,
+/// falling back to the roaming one like Electron does
+fn local_app_data_dir() -> anyhow::Result<PathBuf> {
+    match env_dir("LOCALAPPDATA").or_else(|| env_dir("XDG_DATA_HOME")) {
+        Some(dir) => Ok(dir),
+        None => app_data_dir(),
+    }
+}
+
+fn env_dir(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Folder name used for the game's data, like `getAppName` in the Electron template:
+/// the product name on Windows, the bundle identifier elsewhere
+pub fn app_name<R: Runtime>(app_handle: &AppHandle<R>) -> String {
+    let config = app_handle.config();
+    if cfg!(windows) {
+        config
+            .product_name
+            .clone()
+            .unwrap_or_else(|| app_handle.package_info().name.clone())
+    } else {
+        config.identifier.clone()
+    }
+}
+
+/// The game files, i.e. the `frontendDist` folder of `tauri.conf.json`.
+///
+/// In dev, relative folders are resolved from the crate folder, as the Tauri CLI does.
+/// Release builds embed the files in the executable, so the folder is only found when
+/// it was also shipped with the app: bundled as a resource, or next to the executable.
+fn project_dir<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<PathBuf> {
+    let Some(FrontendDist::Directory(dir)) = &app_handle.config().build.frontend_dist else {
+        return Err(coded(
+            ErrorCode::NotFound,
+            "The project folder is only available when frontendDist is a folder",
+        ));
+    };
+
+    #[cfg(dev)]
+    {
+        Ok(resolve_relative(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")),
+            dir,
+        ))
+    }
+
+    #[cfg(not(dev))]
+    {
+        let name = dir.file_name().unwrap_or(dir.as_os_str());
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(PathBuf::from));
+        [app_handle.path().resource_dir().ok(), exe_dir]
+            .into_iter()
+            .flatten()
+            .map(|folder| folder.join(name))
+            .find(|folder| folder.join("index.html").is_file())
+            .ok_or_else(|| {
+                coded(
+                    ErrorCode::NotFound,
+                    "The game files are embedded in the executable, there is no project folder",
+                )
+            })
+    }
+}
+
+/// Joins `path` to `base`, resolving `..` by hand: canonicalize() would return
+/// `\\?\` paths on Windows
+#[cfg(dev)]
+fn resolve_relative(mut base: PathBuf, path: &std::path::Path) -> PathBuf {
+    use std::path::Component;
+
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                base.pop();
+            }
+            Component::CurDir => {}
+            other => base.push(other),
+        }
+    }
+    base
+}
@@ -0,0 +1,568 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager, Runtime, WebviewWindow, Window, WindowEvent};
+#[cfg(desktop)]
+use tauri::{LogicalPosition, LogicalSize, UserAttentionType};
+use tracing::debug;
+use ts_rs::TS;
+
+use crate::{
+    connections::Connections,
+    error::{coded, ErrorCode},
+    router::{Request, Router},
+};
+
+/// Width and height in logical pixels
+#[derive(Serialize, Deserialize, Debug, TS, JsonSchema)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Position in logical pixels
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Monitor the window is on
+#[derive(Serialize, Debug, TS, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorState {
+    pub name: Option<String>,
+    pub size: Size,
+    pub position: Position,
+    pub scale_factor: f64,
+}
+
+/// Reply of `/window/state`
+#[derive(Serialize, Debug, TS, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowState {
+    pub inner_size: Size,
+    pub outer_size: Size,
+    pub position: Position,
+    pub scale_factor: f64,
+    pub maximized: bool,
+    pub minimized: bool,
+    pub fullscreen: bool,
+    pub focused: bool,
+    pub visible: bool,
+    pub monitor: Option<MonitorState>,
+}
+
+/// Body of the routes that set a single value
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[schemars(rename = "ValueBody_for_{T}")]
+pub struct ValueBody<T> {
+    pub value: T,
+}
+
+/// Value of `/window/set-fullscreen`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum FullscreenMode {
+    Fullscreen,
+    Normal,
+}
+
+/// Body of `/window/set-ignore-mouse-events`
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+pub struct IgnoreMouseEventsBody {
+    pub ignore: bool,
+    /// Accepted for Electron compatibility, has no effect
+    pub forward: Option<bool>,
+}
+
+pub fn register<R: Runtime>(router: Router<R>) -> Router<R> {
+    router
+        .route("/window/maximize", handle_window_maximize::<R>)
+        .route("/window/minimize", handle_window_minimize::<R>)
+        .route("/window/restore", handle_window_restore::<R>)
+        .route("/window/unmaximize", handle_window_unmaximize::<R>)
+        .route(
+            "/window/request-attention",
+            handle_window_request_attention::<R>,
+        )
+        .route(
+            "/window/set-always-on-top",
+            handle_window_set_always_on_top::<R>,
+        )
+        .route("/window/set-height", handle_window_set_height::<R>)
+        .route(
+            "/window/set-maximum-size",
+            handle_window_set_maximum_size::<R>,
+        )
+        .route(
+            "/window/set-minimum-size",
+            handle_window_set_minimum_size::<R>,
+        )
+        .route("/window/set-resizable", handle_window_set_resizable::<R>)
+        .route("/window/set-title", handle_window_set_title::<R>)
+        .route("/window/set-width", handle_window_set_width::<R>)
+        .route("/window/set-x", handle_window_set_x::<R>)
+        .route("/window/set-y", handle_window_set_y::<R>)
+        .route("/window/show-dev-tools", handle_window_show_dev_tools::<R>)
+        .route("/window/set-fullscreen", handle_window_set_fullscreen::<R>)
+        .route(
+            "/window/set-ignore-mouse-events",
+            handle_window_set_ignore_mouse_events::<R>,
+        )
+        .route("/window/state", handle_window_state::<R>)
+}
+
+/// Returns the game window
+pub fn main_window<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<WebviewWindow<R>> {
+    app_handle
+        .get_webview_window("main")
+        .ok_or_else(|| coded(ErrorCode::NotFound, "Main window not found"))
+}
+
+async fn handle_window_maximize<R: Runtime>(request: Request<R>) -> anyhow::Result<()> {
+    debug!("Handling /window/maximize request");
+    let window = main_window(&request.app_handle)?;
+    #[cfg(desktop)] // This block only compiles on desktop targets
+    {
+        window.maximize()?;
+        debug!("Window 'main' maximized.");
+    }
+    #[cfg(mobile)] // This block only compiles on mobile targets
+    {
+        // On mobile, maximize doesn't exist/make sense in the same way.
+        debug!("Window maximize is not supported on mobile. Returning error.");
+        return Err(coded(
+            ErrorCode::UnsupportedPlatform,
+            "Window maximize is not supported on this platform",
+        ));
+    }
+    Ok(())
+}
+
+async fn handle_window_minimize<R: Runtime>(request: Request<R>) -> anyhow::Result<()> {
+    debug!("Handling /window/minimize request");
+    let window = main_window(&request.app_handle)?;
+    #[cfg(desktop)]
+    {
+        window.minimize()?;
+        debug!("Window 'main' minimized.");
+    }
+    #[cfg(mobile)]
+    {
+        debug!("Window minimize is not supported on mobile. Returning error.");
+        return Err(coded(
+            ErrorCode::UnsupportedPlatform,
+            "Window minimize is not supported on this platform",
+        ));
+    }
+    Ok(())
+}
+
+async fn handle_window_restore<R: Runtime>(request: Request<R>) -> anyhow::Result<()> {
+    debug!("Handling /window/restore request");
+    let window = main_window(&request.app_handle)?;
+    #[cfg(desktop)]
+    {
+        if window.is_maximized()? {
+            window.unmaximize()?;
+        }
+        window.set_focus()?;
+        if !window.is_visible()? {
+            window.show()?;
+        }
+        debug!("Window 'main' restored (attempted).");
+    }
+    #[cfg(mobile)]
+    {
+        debug!("Window restore is not supported on mobile. Returning error.");
+        return Err(coded(
+            ErrorCode::UnsupportedPlatform,
+            "Window restore is not supported on this platform",
+        ));
+    }
+    Ok(())
+}
+
+async fn handle_window_unmaximize<R: Runtime>(request: Request<R>) -> anyhow::Result<()> {
+    debug!("Handling /window/unmaximize request");
+    let window = main_window(&request.app_handle)?;
+    #[cfg(desktop)]
+    {
+        window.unmaximize()?;
+        debug!("Window 'main' unmaximized.");
+    }
+    #[cfg(mobile)]
+    {
+        debug!("Window unmaximize is not supported on mobile. Returning error.");
+        return Err(coded(
+            ErrorCode::UnsupportedPlatform,
+            "Window unmaximize is not supported on this platform",
+        ));
+    }
+    Ok(())
+}
+
+/// Error returned by window routes on platforms without window management
+#[cfg(mobile)]
+fn unsupported_on_mobile(action: &str) -> anyhow::Error {
+    debug!(
+        "Window {} is not supported on mobile. Returning error.",
+        action
+    );
+    coded(
+        ErrorCode::UnsupportedPlatform,
+        format!("Window {} is not supported on this platform", action),
+    )
+}
+
+/// Body: `{ value }`
+async fn handle_window_set_title<R: Runtime>(
+    request: Request<R>,
+    body: ValueBody<String>,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-title request");
+    let window = main_window(&request.app_handle)?;
+    let title = body.value;
+    #[cfg(desktop)]
+    window.set_title(&title)?;
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-title"));
+    Ok(())
+}
+
+/// Body: `{ value }`, in logical pixels. The height is kept.
+async fn handle_window_set_width<R: Runtime>(
+    request: Request<R>,
+    body: ValueBody<f64>,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-width request");
+    let window = main_window(&request.app_handle)?;
+    let width = body.value;
+    #[cfg(desktop)]
+    {
+        let size = window
+            .inner_size()?
+            .to_logical::<f64>(window.scale_factor()?);
+        window.set_size(LogicalSize::new(width, size.height))?;
+    }
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-width"));
+    Ok(())
+}
+
+/// Body: `{ value }`, in logical pixels. The width is kept.
+async fn handle_window_set_height<R: Runtime>(
+    request: Request<R>,
+    body: ValueBody<f64>,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-height request");
+    let window = main_window(&request.app_handle)?;
+    let height = body.value;
+    #[cfg(desktop)]
+    {
+        let size = window
+            .inner_size()?
+            .to_logical::<f64>(window.scale_factor()?);
+        window.set_size(LogicalSize::new(size.width, height))?;
+    }
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-height"));
+    Ok(())
+}
+
+/// Body: `{ value }`, in logical pixels. The vertical position is kept.
+async fn handle_window_set_x<R: Runtime>(
+    request: Request<R>,
+    body: ValueBody<f64>,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-x request");
+    let window = main_window(&request.app_handle)?;
+    let x = body.value;
+    #[cfg(desktop)]
+    {
+        let position = window
+            .outer_position()?
+            .to_logical::<f64>(window.scale_factor()?);
+        window.set_position(LogicalPosition::new(x, position.y))?;
+    }
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-x"));
+    Ok(())
+}
+
+/// Body: `{ value }`, in logical pixels. The horizontal position is kept.
+async fn handle_window_set_y<R: Runtime>(
+    request: Request<R>,
+    body: ValueBody<f64>,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-y request");
+    let window = main_window(&request.app_handle)?;
+    let y = body.value;
+    #[cfg(desktop)]
+    {
+        let position = window
+            .outer_position()?
+            .to_logical::<f64>(window.scale_factor()?);
+        window.set_position(LogicalPosition::new(position.x, y))?;
+    }
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-y"));
+    Ok(())
+}
+
+/// Body: `{ width, height }`, in logical pixels
+async fn handle_window_set_minimum_size<R: Runtime>(
+    request: Request<R>,
+    body: Size,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-minimum-size request");
+    let window = main_window(&request.app_handle)?;
+    let Size { width, height } = body;
+    #[cfg(desktop)]
+    window.set_min_size(Some(LogicalSize::new(width, height)))?;
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-minimum-size"));
+    Ok(())
+}
+
+/// Body: `{ width, height }`, in logical pixels
+async fn handle_window_set_maximum_size<R: Runtime>(
+    request: Request<R>,
+    body: Size,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-maximum-size request");
+    let window = main_window(&request.app_handle)?;
+    let Size { width, height } = body;
+    #[cfg(desktop)]
+    window.set_max_size(Some(LogicalSize::new(width, height)))?;
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-maximum-size"));
+    Ok(())
+}
+
+/// Body: `{ value? }`. The Electron handler always enables it, so `value` defaults to `true`.
+async fn handle_window_set_resizable<R: Runtime>(
+    request: Request<R>,
+    body: ValueBody<Option<bool>>,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-resizable request");
+    let window = main_window(&request.app_handle)?;
+    let resizable = body.value.unwrap_or(true);
+    #[cfg(desktop)]
+    window.set_resizable(resizable)?;
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-resizable"));
+    Ok(())
+}
+
+/// Body: `{ value? }`. The Electron handler always enables it, so `value` defaults to `true`.
+async fn handle_window_set_always_on_top<R: Runtime>(
+    request: Request<R>,
+    body: ValueBody<Option<bool>>,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-always-on-top request");
+    let window = main_window(&request.app_handle)?;
+    let always_on_top = body.value.unwrap_or(true);
+    #[cfg(desktop)]
+    window.set_always_on_top(always_on_top)?;
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-always-on-top"));
+    Ok(())
+}
+
+/// Body: `{ value: 'fullscreen' | 'normal' }`
+async fn handle_window_set_fullscreen<R: Runtime>(
+    request: Request<R>,
+    body: ValueBody<FullscreenMode>,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-fullscreen request");
+    let window = main_window(&request.app_handle)?;
+    let fullscreen = body.value == FullscreenMode::Fullscreen;
+    #[cfg(desktop)]
+    window.set_fullscreen(fullscreen)?;
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-fullscreen"));
+    Ok(())
+}
+
+/// Flashes the taskbar entry, like Electron's `flashFrame(true)`
+async fn handle_window_request_attention<R: Runtime>(request: Request<R>) -> anyhow::Result<()> {
+    debug!("Handling /window/request-attention request");
+    let window = main_window(&request.app_handle)?;
+    #[cfg(desktop)]
+    window.request_user_attention(Some(UserAttentionType::Informational))?;
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("request-attention"));
+    Ok(())
+}
+
+/// Body: `{ value }`, `true` to open the dev tools and `false` to close them.
+///
+/// Release builds need the `devtools` feature, as Tauri strips them otherwise.
+async fn handle_window_show_dev_tools<R: Runtime>(
+    request: Request<R>,
+    body: ValueBody<Option<bool>>,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/show-dev-tools request");
+    let window = main_window(&request.app_handle)?;
+    let open = body.value.unwrap_or(false);
+    #[cfg(any(debug_assertions, feature = "devtools"))]
+    {
+        if open {
+            window.open_devtools();
+        } else {
+            window.close_devtools();
+        }
+    }
+    #[cfg(not(any(debug_assertions, feature = "devtools")))]
+    {
+        let _ = (window, open);
+        return Err(coded(
+            ErrorCode::UnsupportedPlatform,
+            "Dev tools are not available in this build (enable the `devtools` feature)",
+        ));
+    }
+    #[allow(unreachable_code)]
+    Ok(())
+}
+
+/// Body: `{ ignore, forward? }`. Makes the window click-through, e.g. for overlays.
+///
+/// `forward` (Electron only passes mouse moves through to the page) has no
+/// Tauri equivalent and is ignored.
+async fn handle_window_set_ignore_mouse_events<R: Runtime>(
+    request: Request<R>,
+    body: IgnoreMouseEventsBody,
+) -> anyhow::Result<()> {
+    debug!("Handling /window/set-ignore-mouse-events request");
+    let window = main_window(&request.app_handle)?;
+    let ignore = body.ignore;
+    #[cfg(desktop)]
+    window.set_ignore_cursor_events(ignore)?;
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("set-ignore-mouse-events"));
+    Ok(())
+}
+
+/// Replies with the main window's geometry and flags, sizes in logical pixels
+async fn handle_window_state<R: Runtime>(request: Request<R>) -> anyhow::Result<WindowState> {
+    debug!("Handling /window/state request");
+    let window = main_window(&request.app_handle)?;
+    #[cfg(desktop)]
+    return window_state(&window);
+    #[cfg(mobile)]
+    return Err(unsupported_on_mobile("state"));
+}
+
+#[cfg(desktop)]
+fn window_state<R: Runtime>(window: &WebviewWindow<R>) -> anyhow::Result<WindowState> {
+    let scale_factor = window.scale_factor()?;
+    let inner_size = window.inner_size()?.to_logical::<f64>(scale_factor);
+    let outer_size = window.outer_size()?.to_logical::<f64>(scale_factor);
+    let position = window.outer_position()?.to_logical::<f64>(scale_factor);
+    let monitor = window.current_monitor()?.map(|monitor| {
+        let size = monitor.size().to_logical::<f64>(monitor.scale_factor());
+        let position = monitor.position().to_logical::<f64>(monitor.scale_factor());
+        MonitorState {
+            name: monitor.name().cloned(),
+            size: Size {
+                width: size.width,
+                height: size.height,
+            },
+            position: Position {
+                x: position.x,
+                y: position.y,
+            },
+            scale_factor: monitor.scale_factor(),
+        }
+    });
+
+    Ok(WindowState {
+        inner_size: Size {
+            width: inner_size.width,
+            height: inner_size.height,
+        },
+        outer_size: Size {
+            width: outer_size.width,
+            height: outer_size.height,
+        },
+        position: Position {
+            x: position.x,
+            y: position.y,
+        },
+        scale_factor,
+        maximized: window.is_maximized()?,
+        minimized: window.is_minimized()?,
+        fullscreen: window.is_fullscreen()?,
+        focused: window.is_focused()?,
+        visible: window.is_visible()?,
+        monitor,
+    })
+}
+
+// --- Window events ---
+
+/// Pushes changes of the main window to connected clients, so games don't have to poll:
+/// `/window/resized` and `/window/scale-changed` carry the new inner size (logical pixels),
+/// `/window/moved` the new position, `/window/focus` and `/window/blur` an empty body.
+/// Close requests are handed to [`super::close::on_close_requested`].
+pub fn on_window_event<R: Runtime>(window: &Window<R>, event: &WindowEvent) {
+    if window.label() != "main" {
+        return;
+    }
+    if let WindowEvent::CloseRequested { api, .. } = event {
+        super::close::on_close_requested(window, api);
+        return;
+    }
+    let Some(events) = window.try_state::<Connections>() else {
+        return;
+    };
+    let scale_factor = window.scale_factor().unwrap_or(1.0);
+
+    match event {
+        WindowEvent::Resized(size) => {
+            let size = size.to_logical::<f64>(scale_factor);
+            events.broadcast(
+                "/window/resized",
+                Size {
+                    width: size.width,
+                    height: size.height,
+                },
+            );
+        }
+        WindowEvent::Moved(position) => {
+            let position = position.to_logical::<f64>(scale_factor);
+            events.broadcast(
+                "/window/moved",
+                Position {
+                    x: position.x,
+                    y: position.y,
+                },
+            );
+        }
+        WindowEvent::Focused(true) => {
+            events.broadcast("/window/focus", json!({}));
+        }
+        WindowEvent::Focused(false) => {
+            events.broadcast("/window/blur", json!({}));
+        }
+        WindowEvent::ScaleFactorChanged {
+            scale_factor,
+            new_inner_size,
+            ..
+        } => {
+            let size = new_inner_size.to_logical::<f64>(*scale_factor);
+            events.broadcast(
+                "/window/scale-changed",
+                json!({
+                    "scaleFactor": scale_factor,
+                    "width": size.width,
+                    "height": size.height,
+                }),
+            );
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,56 @@
+use serde_json::Value;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tracing::{debug, warn};
+
+use crate::{
+    connections::Connections, protocol::IncomingMessage, router::Router, shutdown::Shutdown,
+};
+
+/// Tauri event carrying the messages pushed to a game using IPC, as `{ url, body }`
+pub const EVENT: &str = "pipelab://event";
+
+/// Sends a request to the handlers of the WebSocket server, for a game running in
+/// the app's own webview:
+///
+/// ```js
+/// const { invoke } = window.__TAURI__.core
+/// const reply = await invoke('pipelab_request', { url: '/fs/exist', body: { path } })
+/// window.__TAURI__.event.listen('pipelab://event', ({ payload }) => { /* { url, body } */ })
+/// ```
+///
+/// Resolves with the reply body, error bodies included, as the socket would send it.
+/// There is no port or token: Tauri only lets the app's own pages call commands.
+/// Streams and `/cancel` need the socket, a streamed request gets the whole result
+/// in one reply instead.
+#[tauri::command]
+pub async fn pipelab_request<R: Runtime>(
+    app_handle: AppHandle<R>,
+    url: String,
+    body: Option<Value>,
+) -> Value {
+    debug!("Received IPC request for {}", url);
+
+    let router = app_handle.state::<Arc<Router<R>>>().inner().clone();
+    app_handle.state::<Connections>().activate_ipc();
+    let _guard = app_handle.state::<Shutdown>().track_request();
+    let message = IncomingMessage {
+        url,
+        correlation_id: None,
+        body,
+        payload: None,
+    };
+    router.dispatch(message, app_handle, None).await.body
+}
+
+/// Emits the events pushed to clients as [`EVENT`] too, once the game uses IPC
+pub fn forward_events<R: Runtime>(app_handle: &AppHandle<R>) {
+    let emitter = app_handle.clone();
+    app_handle
+        .state::<Connections>()
+        .set_ipc_sink(move |event| {
+            if let Err(e) = emitter.emit(EVENT, event) {
+                warn!("Failed to emit event '{}': {}", event.url, e);
+            }
+        });
+}
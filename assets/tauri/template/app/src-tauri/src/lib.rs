@@ -1,54 +1,48 @@
-use futures_util::{
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt,
+use futures_util::{stream::SplitStream, StreamExt};
+use serde_json::{json, Value};
+use std::{io::Write, net::SocketAddr, sync::Arc, time::Duration};
+use tauri::{async_runtime, AppHandle, Manager, Runtime, WebviewWindowBuilder, Wry};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{
+    accept_hdr_async,
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request as HandshakeRequest, Response},
+        http::{header::ORIGIN, StatusCode},
+        Message,
+    },
+    WebSocketStream,
 };
-use serde::{Deserialize, Serialize};
-use serde_json::Value; // Using Value for flexibility in body initially
-use std::{net::SocketAddr, sync::Arc};
-use tauri::{
-    async_runtime, webview::WebviewWindowBuilder, AppHandle, Manager, Runtime, WebviewUrl,
+use tracing::{debug, error, field, info, info_span, trace, warn, Instrument};
+
+pub mod auth;
+pub mod bindings;
+pub mod config;
+pub mod connections;
+pub mod error;
+pub mod handlers;
+pub mod ipc;
+pub mod logging;
+pub mod origin;
+pub mod outbox;
+pub mod protocol;
+pub mod router;
+pub mod shutdown;
+pub mod startup;
+pub mod stream;
+
+use auth::{session_from_query, token_from_query, AuthToken, AUTH_URL};
+use config::{LogConfig, PipelabConfig, ServerConfig};
+use connections::{ConnectionId, Connections};
+use error::ErrorCode;
+use handlers::close::PendingClose;
+use origin::AllowedOrigins;
+use outbox::{write_messages, Outbox};
+use protocol::{
+    decode_binary_frame, encode_binary_frame, EventMessage, IncomingMessage, ResponseMessage,
+    FEATURES, SESSION_URL,
 };
-use tokio::{
-    net::{TcpListener, TcpStream},
-    sync::Mutex, // Using Mutex for the writer part
-};
-use tokio_tungstenite::{accept_async, tungstenite::Message, WebSocketStream};
-// Note: Removed `use anyhow::{Error};` as it's unused when using anyhow::Result<()>
-
-// --- Message Structures ---
-
-/// Generic structure for incoming WebSocket messages
-#[derive(Deserialize, Debug)]
-struct IncomingMessage {
-    url: String,
-    #[serde(rename = "correlationId")] // Match JS naming
-    correlation_id: Option<String>, // Optional correlation ID
-    body: Option<Value>, // Use Option<Value> to handle cases where body might be missing
-}
-
-/// Generic structure for outgoing WebSocket responses
-#[derive(Serialize, Debug)]
-struct ResponseMessage<T: Serialize> {
-    url: String,
-    #[serde(rename = "correlationId")]
-    correlation_id: Option<String>, // Echo back the correlation ID
-    body: T, // Generic body for success or error
-}
-
-/// Example structure for a success response body
-#[derive(Serialize, Debug)]
-struct SuccessBody<T: Serialize> {
-    success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")] // Don't serialize data if it's None
-    data: Option<T>, // Make data optional for responses that don't need it
-}
-
-/// Example structure for an error response body
-#[derive(Serialize, Debug)]
-struct ErrorBody {
-    success: bool,
-    error: String,
-}
+use router::{error_body, Router};
+use shutdown::Shutdown;
 
 // --- WebSocket Handling ---
 
@@ -56,542 +50,456 @@ struct ErrorBody {
 async fn handle_websocket<R: Runtime>(
     stream: TcpStream,
     app_handle: AppHandle<R>, // Pass AppHandle for Tauri interaction
+    router: Arc<Router<R>>,
 ) {
     let addr = stream
         .peer_addr()
         .expect("Connected stream should have peer address");
-    println!("New WebSocket connection from: {}", addr);
+    debug!("New WebSocket connection");
+
+    let origins = app_handle.state::<AllowedOrigins>();
+    let token = app_handle.state::<AuthToken>();
+    let mut authenticated = false;
+    let mut session = None;
+    // The error type is set by tungstenite
+    #[allow(clippy::result_large_err)]
+    let check_handshake = |request: &HandshakeRequest, response: Response| {
+        let origin = request
+            .headers()
+            .get(ORIGIN)
+            .and_then(|origin| origin.to_str().ok());
+        match origin {
+            Some(origin) if origins.allows(origin) => {}
+            Some(origin) => {
+                warn!("Rejected handshake: origin '{}' is not allowed", origin);
+                return Err(reject(StatusCode::FORBIDDEN, "Origin not allowed"));
+            }
+            // Browsers always send one, so this isn't a web page; the token is still checked
+            None => debug!("Handshake without origin"),
+        }
 
-    match accept_async(stream).await {
+        session = session_from_query(request.uri().query()).map(String::from);
+        match token_from_query(request.uri().query()) {
+            Some(candidate) if token.verify(candidate) => authenticated = true,
+            Some(_) => return Err(reject(StatusCode::UNAUTHORIZED, "Invalid token")),
+            // The client may still send it in its first message
+            None => {}
+        }
+        Ok(response)
+    };
+
+    match accept_hdr_async(stream, check_handshake).await {
         Ok(ws_stream) => {
-            println!("WebSocket connection established: {}", addr);
-            let (write, read) = ws_stream.split();
-            let writer = Arc::new(Mutex::new(write));
-            process_messages(read, writer.clone(), app_handle, addr).await;
-            println!("WebSocket connection closed: {}", addr);
+            info!("WebSocket connection established");
+            let (write, mut read) = ws_stream.split();
+            // One task owns the writing half, everything else queues messages for it
+            let config = &app_handle.state::<PipelabConfig>().connection;
+            let (outbox, receiver) = outbox::outbox(config);
+            let writer = tokio::spawn(write_messages(write, receiver).in_current_span());
+
+            let auth_timeout = Duration::from_millis(config.auth_timeout_ms);
+            if !authenticated
+                && !authenticate(&mut read, &outbox, &token, auth_timeout, &mut session).await
+            {
+                warn!("Rejected unauthenticated client");
+                // Let the writer flush the error reply, then close
+                drop(outbox);
+                let _ = writer.await;
+                return;
+            }
+
+            let connections = app_handle.state::<Connections>();
+            let Some(connection_id) =
+                open_session(&connections, addr, session.as_deref(), &outbox).await
+            else {
+                outbox.close();
+                let _ = writer.await;
+                return;
+            };
+            tracing::Span::current().record("id", connection_id);
+            process_messages(
+                read,
+                outbox.clone(),
+                app_handle.clone(),
+                router,
+                connection_id,
+            )
+            .await;
+            outbox.close();
+            let unsent = writer.await.unwrap_or_default();
+            close_session(&app_handle, connection_id, &outbox, unsent);
+            info!("WebSocket connection closed");
         }
         Err(e) => {
-            eprintln!("Error during WebSocket handshake for {}: {}", addr, e);
+            warn!("Error during WebSocket handshake: {}", e);
         }
     }
 }
 
-/// Processes messages received from a single client
-async fn process_messages<R: Runtime>(
-    mut read: SplitStream<WebSocketStream<TcpStream>>,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-    app_handle: AppHandle<R>,
+/// Builds the HTTP response sent to a client whose handshake is refused
+fn reject(status: StatusCode, reason: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Resumes the session the client asked for, or starts a new one, and tells the
+/// client with a `/session` event before replaying what it missed
+async fn open_session(
+    connections: &Connections,
     addr: SocketAddr,
-) {
-    while let Some(message_result) = read.next().await {
-        match message_result {
-            Ok(msg) => {
-                match msg {
-                    Message::Text(text) => {
-                        println!("Received text from {}: {}", addr, text);
-                        match serde_json::from_str::<IncomingMessage>(&text) {
-                            Ok(parsed_message) => {
-                                let writer_clone = writer.clone();
-                                let app_handle_clone = app_handle.clone();
-                                let url = parsed_message.url.clone();
-                                let correlation_id = parsed_message.correlation_id.clone();
-
-                                tokio::spawn(async move {
-                                    let writer_for_route = writer_clone.clone();
-                                    // Using anyhow::Result allows easy error propagation with `?`
-                                    if let Err(e) = route_message(
-                                        parsed_message,
-                                        writer_for_route,
-                                        app_handle_clone,
-                                    )
-                                    .await
-                                    {
-                                        eprintln!(
-                                            "Error handling message for url '{}': {}",
-                                            url, e
-                                        );
-                                        let error_response = ResponseMessage {
-                                            url,
-                                            correlation_id,
-                                            body: ErrorBody {
-                                                success: false,
-                                                error: e.to_string(),
-                                            },
-                                        };
-                                        if let Ok(json_response) =
-                                            serde_json::to_string(&error_response)
-                                        {
-                                            let mut w = writer_clone.lock().await;
-                                            if let Err(send_err) =
-                                                w.send(Message::Text(json_response)).await
-                                            {
-                                                eprintln!(
-                                                    "Failed to send error response: {}",
-                                                    send_err
-                                                );
-                                            }
-                                        }
-                                    }
-                                });
-                            }
-                            Err(e) => {
-                                eprintln!(
-                                    "Failed to parse JSON from {}: {}. Message: {}",
-                                    addr, e, text
-                                );
-                                let response = ResponseMessage {
-                                    url: "unknown".to_string(),
-                                    correlation_id: None,
-                                    body: ErrorBody {
-                                        success: false,
-                                        error: format!("Invalid JSON format: {}", e),
-                                    },
-                                };
-                                if let Ok(json_response) = serde_json::to_string(&response) {
-                                    let mut w = writer.lock().await;
-                                    if let Err(send_err) =
-                                        w.send(Message::Text(json_response)).await
-                                    {
-                                        eprintln!(
-                                            "Failed to send parse error response: {}",
-                                            send_err
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Message::Binary(_) => println!("Received binary data from {} (ignored)", addr),
-                    Message::Ping(ping_data) => {
-                        println!("Received Ping from {}", addr);
-                        let mut w = writer.lock().await;
-                        if let Err(e) = w.send(Message::Pong(ping_data)).await {
-                            eprintln!("Failed to send Pong: {}", e);
-                        }
-                    }
-                    Message::Pong(_) => println!("Received Pong from {}", addr),
-                    Message::Close(_) => {
-                        println!("Received Close frame from {}", addr);
-                        break;
-                    }
-                    Message::Frame(_) => println!("Received raw Frame from {} (ignored)", addr),
-                }
-            }
+    requested: Option<&str>,
+    outbox: &Outbox,
+) -> Option<ConnectionId> {
+    let resumed = requested.and_then(|session| connections.resume(session, addr, outbox.clone()));
+    let (connection_id, session) = match resumed {
+        Some(resumed) => resumed,
+        None => match connections.register(addr, outbox.clone()) {
+            Ok(registered) => registered,
             Err(e) => {
-                eprintln!("WebSocket error reading message from {}: {}", addr, e);
-                break;
+                warn!("Failed to start a session: {:#}", e);
+                return None;
             }
-        }
+        },
+    };
+    if session.resumed {
+        info!("Session resumed, {} event(s) dropped", session.dropped);
+    } else if requested.is_some() {
+        info!("Session to resume not found, starting a new one");
     }
-}
 
-/// Routes the parsed message to the appropriate handler
-// --- Fix: Changed return type to anyhow::Result<()> ---
-async fn route_message<R: Runtime>(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-    app_handle: AppHandle<R>,
-) -> anyhow::Result<()> {
-    // Use anyhow::Result for easier error handling
-    println!("Routing message for URL: {}", message.url);
-
-    match message.url.as_str() {
-        "/paths" => handle_paths(message, writer, app_handle).await?,
-        "/fs/file/write" => handle_fs_write(message, writer, app_handle).await?,
-        "/window/maximize" => handle_window_maximize(message, writer, app_handle).await?,
-        "/fs/file/read" => handle_not_implemented(message, writer).await?,
-        "/fs/file/read/binary" => handle_not_implemented(message, writer).await?,
-        "/fs/folder/create" => handle_not_implemented(message, writer).await?,
-        "/window/minimize" => handle_window_minimize(message, writer, app_handle).await?,
-        "/window/request-attention" => handle_not_implemented(message, writer).await?,
-        "/window/restore" => handle_window_restore(message, writer, app_handle).await?,
-        "/dialog/folder" => handle_not_implemented(message, writer).await?,
-        "/dialog/open" => handle_not_implemented(message, writer).await?,
-        "/dialog/save" => handle_not_implemented(message, writer).await?,
-        "/window/set-always-on-top" => handle_not_implemented(message, writer).await?,
-        "/window/set-height" => handle_not_implemented(message, writer).await?,
-        "/window/set-maximum-size" => handle_not_implemented(message, writer).await?,
-        "/window/set-minimum-size" => handle_not_implemented(message, writer).await?,
-        "/window/set-resizable" => handle_not_implemented(message, writer).await?,
-        "/window/set-title" => handle_not_implemented(message, writer).await?,
-        "/window/set-width" => handle_not_implemented(message, writer).await?,
-        "/window/set-x" => handle_not_implemented(message, writer).await?,
-        "/window/set-y" => handle_not_implemented(message, writer).await?,
-        "/window/show-dev-tools" => handle_not_implemented(message, writer).await?,
-        "/window/unmaximize" => handle_window_unmaximize(message, writer, app_handle).await?,
-        "/window/set-fullscreen" => handle_not_implemented(message, writer).await?,
-        "/engine" => handle_engine(message, writer).await?,
-        "/open" => handle_not_implemented(message, writer).await?,
-        "/show-in-explorer" => handle_not_implemented(message, writer).await?,
-        "/run" => handle_not_implemented(message, writer).await?,
-        "/fs/copy" => handle_not_implemented(message, writer).await?,
-        "/fs/delete" => handle_not_implemented(message, writer).await?,
-        "/fs/exist" => handle_not_implemented(message, writer).await?,
-        "/fs/list" => handle_not_implemented(message, writer).await?,
-        "/fs/file/size" => handle_not_implemented(message, writer).await?,
-        "/fs/move" => handle_not_implemented(message, writer).await?,
-        "/steam/raw" => handle_not_implemented(message, writer).await?,
-        "/discord/set-activity" => handle_not_implemented(message, writer).await?,
-        "/infos" => handle_not_implemented(message, writer).await?,
-        "/exit" => handle_exit(message, writer, app_handle).await?,
-
-        _ => {
-            println!("Received unhandled URL: {}", message.url);
-            let response = ResponseMessage {
-                url: message.url.clone(),
-                correlation_id: message.correlation_id.clone(),
-                body: ErrorBody {
-                    success: false,
-                    error: format!("Unhandled URL: {}", message.url),
-                },
-            };
-            let json_response = serde_json::to_string(&response)?;
-            let mut w = writer.lock().await;
-            w.send(Message::Text(json_response)).await?;
+    let event = EventMessage {
+        url: SESSION_URL.to_string(),
+        body: session,
+    };
+    let event = match serde_json::to_string(&event) {
+        Ok(event) => event,
+        Err(e) => {
+            warn!("Failed to serialize the session: {}", e);
+            connections.unregister(connection_id);
+            return None;
         }
+    };
+    outbox.send(Message::Text(event)).await;
+    let replayed = connections.replay(connection_id, outbox).await;
+    if replayed > 0 {
+        debug!("Replayed {} missed message(s)", replayed);
     }
-    Ok(())
+    Some(connection_id)
 }
 
-// --- Example Handler Implementations (Stubs) ---
-
-// --- Fix: Changed return type for all handlers to anyhow::Result<()> ---
-
-async fn handle_not_implemented(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-) -> anyhow::Result<()> {
-    // Use anyhow::Result
-    println!("Handler not implemented for URL: {}", message.url);
-    let response = ResponseMessage {
-        url: message.url.clone(),
-        correlation_id: message.correlation_id.clone(),
-        body: ErrorBody {
-            success: false,
-            error: format!("Feature not implemented: {}", message.url),
-        },
-    };
-    let json_response = serde_json::to_string(&response)?;
-    let mut w = writer.lock().await;
-    w.send(Message::Text(json_response)).await?;
-    Ok(())
-}
+/// Keeps the session of a client that went away for `session.gracePeriodMs`, so it
+/// can resume it, then ends it. Its requests keep running until then.
+fn close_session<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    connection_id: ConnectionId,
+    outbox: &Outbox,
+    unsent: Vec<Message>,
+) {
+    let connections = app_handle.state::<Connections>();
+    let grace_period =
+        Duration::from_millis(app_handle.state::<PipelabConfig>().session.grace_period_ms);
+    // Does nothing if the client already resumed it from another connection
+    connections.detach(connection_id, outbox, unsent);
+    if grace_period.is_zero() || app_handle.state::<Shutdown>().is_stopping() {
+        connections.expire(connection_id, Duration::ZERO);
+        return;
+    }
 
-async fn handle_engine(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-) -> anyhow::Result<()> {
-    // Use anyhow::Result
-    println!(
-        "Handling /engine request. Body (if any): {:?}",
-        message.body
+    let app_handle = app_handle.clone();
+    tokio::spawn(
+        async move {
+            tokio::time::sleep(grace_period).await;
+            if app_handle
+                .state::<Connections>()
+                .expire(connection_id, grace_period)
+            {
+                info!("Session expired");
+            }
+        }
+        .in_current_span(),
     );
-    let response = ResponseMessage {
-        url: message.url.clone(),
-        correlation_id: message.correlation_id.clone(),
-        body: SuccessBody {
-            success: true,
-            data: Option::<()>::None,
-        },
-    };
-    let json_response = serde_json::to_string(&response)?;
-    let mut w = writer.lock().await;
-    w.send(Message::Text(json_response)).await?;
-    Ok(())
 }
 
-async fn handle_paths<R: Runtime>(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-    app_handle: AppHandle<R>,
-) -> anyhow::Result<()> {
-    // Use anyhow::Result
-    println!("Handling /paths request. Body (if any): {:?}", message.body);
-
-    // --- Fix: Use ok_or_else correctly on Option ---
-    // let user_data_path = app_handle.path().app_data_dir()
-    //     .ok_or_else(|| anyhow::anyhow!("Could not get app data dir"))?; // ok_or_else is called on Option<PathBuf>
-    // let documents_path = dirs::document_dir()
-    //     .ok_or_else(|| anyhow::anyhow!("Could not get documents dir"))?; // ok_or_else is called on Option<PathBuf>
-
-    let data = serde_json::json!({
-        "appData": "data", // user_data_path,
-        "documents": "data", // documents_path,
-    });
-
-    let response = ResponseMessage {
-        url: message.url.clone(),
-        correlation_id: message.correlation_id.clone(),
-        body: SuccessBody {
-            success: true,
-            data: Some(data),
-        },
+/// Waits for the `/auth` message of a client that didn't send the token in the
+/// handshake, and answers it. Returns whether the token was right, and sets
+/// `session` when the client asks to resume one.
+async fn authenticate(
+    read: &mut SplitStream<WebSocketStream<TcpStream>>,
+    outbox: &Outbox,
+    token: &AuthToken,
+    timeout: Duration,
+    session: &mut Option<String>,
+) -> bool {
+    let first_message = tokio::time::timeout(timeout, async {
+        while let Some(Ok(message)) = read.next().await {
+            match message {
+                Message::Text(text) => return Some(text),
+                Message::Ping(ping_data) => {
+                    outbox.send(Message::Pong(ping_data)).await;
+                }
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        None
+    })
+    .await;
+    let Ok(Some(text)) = first_message else {
+        warn!("Client did not authenticate in time");
+        return false;
     };
-    let json_response = serde_json::to_string(&response)?;
-    let mut w = writer.lock().await;
-    w.send(Message::Text(json_response)).await?;
-    Ok(())
-}
 
-async fn handle_fs_write<R: Runtime>(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-    _app_handle: AppHandle<R>,
-) -> anyhow::Result<()> {
-    // Use anyhow::Result
-    println!("Handling /fs/file/write request.");
-
-    let body_value = message
-        .body
-        .ok_or_else(|| anyhow::anyhow!("Missing request body for /fs/file/write"))?;
-    let path = body_value
-        .get("path")
-        .and_then(Value::as_str)
-        .ok_or_else(|| anyhow::anyhow!("Missing 'path' field in body"))?;
-    let content = body_value
-        .get("content")
+    let message = serde_json::from_str::<IncomingMessage>(&text).ok();
+    let accepted = message.as_ref().is_some_and(|message| {
+        message.url == AUTH_URL
+            && message
+                .body
+                .as_ref()
+                .and_then(|body| body.get("token"))
+                .and_then(Value::as_str)
+                .is_some_and(|candidate| token.verify(candidate))
+    });
+    if let Some(requested) = message
+        .as_ref()
+        .and_then(|message| message.body.as_ref())
+        .and_then(|body| body.get("session"))
         .and_then(Value::as_str)
-        .ok_or_else(|| anyhow::anyhow!("Missing 'content' field in body"))?;
-
-    println!(
-        "Attempting to write to path: '{}', Content length: {}",
-        path,
-        content.len()
-    );
-    // tokio::fs::write(path, content).await?; // Add actual file writing logic here
-
+    {
+        *session = Some(requested.to_string());
+    }
+    let body = if accepted {
+        json!({ "success": true, "features": FEATURES })
+    } else {
+        error_body(
+            ErrorCode::Unauthorized,
+            format!(
+                "Unauthorized: send {{ url: '{}', body: {{ token }} }} first",
+                AUTH_URL
+            ),
+        )
+    };
     let response = ResponseMessage {
-        url: message.url.clone(),
-        correlation_id: message.correlation_id.clone(),
-        body: SuccessBody {
-            success: true,
-            data: Option::<()>::None,
-        },
+        url: AUTH_URL.to_string(),
+        correlation_id: message.and_then(|message| message.correlation_id),
+        body,
+        seq: None,
+        end: None,
+        payload: None,
     };
-    let json_response = serde_json::to_string(&response)?;
-    let mut w = writer.lock().await;
-    w.send(Message::Text(json_response)).await?;
-    Ok(())
+    if let Err(e) = send_response(outbox, &response).await {
+        warn!("Failed to send auth response: {}", e);
+    }
+    accepted
 }
 
-async fn handle_window_maximize<R: Runtime>(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
+/// Processes messages received from a single client, until it disconnects
+/// or its outbox is closed
+async fn process_messages<R: Runtime>(
+    mut read: SplitStream<WebSocketStream<TcpStream>>,
+    outbox: Outbox,
     app_handle: AppHandle<R>,
-) -> anyhow::Result<()> {
-    // Use anyhow::Result
-    println!("Handling /window/maximize request");
-    match app_handle.get_webview_window("main") {
-        Some(window) => {
-            #[cfg(desktop)] // This block only compiles on desktop targets
-            {
-                window.maximize()?; // This call is safe here
-                println!("Window 'main' maximized.");
-            }
-            #[cfg(mobile)] // This block only compiles on mobile targets
-            {
-                // On mobile, maximize doesn't exist/make sense in the same way.
-                println!("Window maximize is not supported on mobile. Returning error.");
-                // Return an error indicating the operation is not supported on this platform.
-                return Err(anyhow::anyhow!(
-                    "Window maximize is not supported on this platform"
-                ));
+    router: Arc<Router<R>>,
+    connection_id: ConnectionId,
+) {
+    loop {
+        let message_result = tokio::select! {
+            message = read.next() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            _ = outbox.closed() => break,
+        };
+        let parsed = match message_result {
+            Ok(msg) => match msg {
+                Message::Text(text) => {
+                    trace!("Received {} bytes of text", text.len());
+                    serde_json::from_str::<IncomingMessage>(&text)
+                        .map_err(|e| format!("Invalid JSON format: {}", e))
+                }
+                Message::Binary(data) => {
+                    debug!("Received {} bytes of binary data", data.len());
+                    decode_binary_frame(&data).map_err(|e| format!("Invalid binary frame: {}", e))
+                }
+                Message::Ping(ping_data) => {
+                    trace!("Received Ping");
+                    if !outbox.send(Message::Pong(ping_data)).await {
+                        warn!("Failed to send Pong: connection closed");
+                    }
+                    continue;
+                }
+                Message::Pong(_) => {
+                    trace!("Received Pong");
+                    continue;
+                }
+                Message::Close(_) => {
+                    debug!("Received Close frame");
+                    break;
+                }
+                Message::Frame(_) => {
+                    trace!("Received raw Frame (ignored)");
+                    continue;
+                }
+            },
+            Err(e) => {
+                warn!("WebSocket error reading message: {}", e);
+                break;
             }
+        };
 
-            let response = ResponseMessage {
-                url: message.url.clone(),
-                correlation_id: message.correlation_id.clone(),
-                body: SuccessBody {
-                    success: true,
-                    data: Option::<()>::None,
-                },
-            };
-            let json_response = serde_json::to_string(&response)?;
-            let mut w = writer.lock().await;
-            w.send(Message::Text(json_response)).await?;
-        }
-        None => {
-            return Err(anyhow::anyhow!("Main window not found")); // Return anyhow error
-        }
-    }
-    Ok(())
-}
-
-async fn handle_window_minimize<R: Runtime>(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-    app_handle: AppHandle<R>,
-) -> anyhow::Result<()> {
-    // Use anyhow::Result
-    println!("Handling /window/minimize request");
-    match app_handle.get_webview_window("main") {
-        Some(window) => {
-            #[cfg(desktop)]
-            {
-                window.minimize()?;
-                println!("Window 'main' minimized.");
+        match parsed {
+            Ok(parsed_message) => {
+                let app_handle_clone = app_handle.clone();
+                let router_clone = router.clone();
+                // Held until the reply is queued, so it goes out before the Close frame
+                let guard = app_handle.state::<Shutdown>().track_request();
+
+                tokio::spawn(
+                    async move {
+                        let _guard = guard;
+                        let response = router_clone
+                            .dispatch(
+                                parsed_message,
+                                app_handle_clone.clone(),
+                                Some(connection_id),
+                            )
+                            .await;
+                        if let Err(e) =
+                            send_reply(&app_handle_clone, connection_id, &response).await
+                        {
+                            warn!("Failed to send response for url '{}': {}", response.url, e);
+                        }
+                    }
+                    .in_current_span(),
+                );
             }
-            #[cfg(mobile)]
-            {
-                println!("Window minimize is not supported on mobile. Returning error.");
-                return Err(anyhow::anyhow!(
-                    "Window minimize is not supported on this platform"
-                ));
+            Err(error) => {
+                warn!("Failed to parse message: {}", error);
+                let response = ResponseMessage {
+                    url: "unknown".to_string(),
+                    correlation_id: None,
+                    body: error_body(ErrorCode::InvalidBody, error),
+                    seq: None,
+                    end: None,
+                    payload: None,
+                };
+                if let Err(send_err) = send_response(&outbox, &response).await {
+                    warn!("Failed to send parse error response: {}", send_err);
+                }
             }
-            let response = ResponseMessage {
-                url: message.url.clone(),
-                correlation_id: message.correlation_id.clone(),
-                body: SuccessBody {
-                    success: true,
-                    data: Option::<()>::None,
-                },
-            };
-            let json_response = serde_json::to_string(&response)?;
-            let mut w = writer.lock().await;
-            w.send(Message::Text(json_response)).await?;
-        }
-        None => {
-            return Err(anyhow::anyhow!("Main window not found"));
         }
     }
-    Ok(())
 }
 
-async fn handle_window_restore<R: Runtime>(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-    app_handle: AppHandle<R>,
+/// Serializes a response and queues it for the client, in a binary frame
+/// when it carries a payload
+async fn send_response(
+    outbox: &Outbox,
+    response: &ResponseMessage<serde_json::Value>,
 ) -> anyhow::Result<()> {
-    // Use anyhow::Result
-    println!("Handling /window/restore request");
-    match app_handle.get_webview_window("main") {
-        Some(window) => {
-            #[cfg(desktop)]
-            {
-                if window.is_maximized()? {
-                    window.unmaximize()?;
-                }
-                window.set_focus()?;
-                if !window.is_visible()? {
-                    window.show()?;
-                }
-                println!("Window 'main' restored (attempted).");
-            }
-            #[cfg(mobile)]
-            {
-                println!("Window restore is not supported on mobile. Returning error.");
-                return Err(anyhow::anyhow!(
-                    "Window restore is not supported on this platform"
-                ));
-            }
-
-            let response = ResponseMessage {
-                url: message.url.clone(),
-                correlation_id: message.correlation_id.clone(),
-                body: SuccessBody {
-                    success: true,
-                    data: Option::<()>::None,
-                },
-            };
-            let json_response = serde_json::to_string(&response)?;
-            let mut w = writer.lock().await;
-            w.send(Message::Text(json_response)).await?;
-        }
-        None => {
-            return Err(anyhow::anyhow!("Main window not found"));
-        }
+    if !outbox.send(encode_response(response)?).await {
+        return Err(anyhow::anyhow!("Connection closed"));
     }
     Ok(())
 }
 
-async fn handle_window_unmaximize<R: Runtime>(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-    app_handle: AppHandle<R>,
+/// Queues the reply to a request on the client's session, so it still reaches
+/// a client that reconnects before it is ready
+async fn send_reply<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    connection_id: ConnectionId,
+    response: &ResponseMessage<serde_json::Value>,
 ) -> anyhow::Result<()> {
-    // Use anyhow::Result
-    println!("Handling /window/unmaximize request");
-    match app_handle.get_webview_window("main") {
-        Some(window) => {
-            #[cfg(desktop)]
-            {
-                window.unmaximize()?; // <-- Your original line, now conditional
-                println!("Window 'main' unmaximized.");
-            }
-            #[cfg(mobile)]
-            {
-                println!("Window unmaximize is not supported on mobile. Returning error.");
-                return Err(anyhow::anyhow!(
-                    "Window unmaximize is not supported on this platform"
-                ));
-            }
-            let response = ResponseMessage {
-                url: message.url.clone(),
-                correlation_id: message.correlation_id.clone(),
-                body: SuccessBody {
-                    success: true,
-                    data: Option::<()>::None,
-                },
-            };
-            let json_response = serde_json::to_string(&response)?;
-            let mut w = writer.lock().await;
-            w.send(Message::Text(json_response)).await?;
-        }
-        None => {
-            return Err(anyhow::anyhow!("Main window not found"));
-        }
+    let message = encode_response(response)?;
+    if !app_handle
+        .state::<Connections>()
+        .reply(connection_id, response.correlation_id.clone(), message)
+        .await
+    {
+        return Err(anyhow::anyhow!("Session closed"));
     }
     Ok(())
 }
 
-async fn handle_exit<R: Runtime>(
-    message: IncomingMessage,
-    writer: Arc<Mutex<SplitSink<WebSocketStream<TcpStream>, Message>>>,
-    app_handle: AppHandle<R>,
-) -> anyhow::Result<()> {
-    // Use anyhow::Result
-    println!("Handling /exit request");
-    let response = ResponseMessage {
-        url: message.url.clone(),
-        correlation_id: message.correlation_id.clone(),
-        body: SuccessBody {
-            success: true,
-            data: Option::<()>::None,
-        },
-    };
-    let json_response = serde_json::to_string(&response)?;
-    let mut w = writer.lock().await;
-    w.send(Message::Text(json_response)).await?;
-
-    app_handle.exit(0);
-    // TODO: support exit code
-    // app_handle.exit(message.body.code);
-    Ok(())
+fn encode_response(response: &ResponseMessage<serde_json::Value>) -> anyhow::Result<Message> {
+    Ok(match &response.payload {
+        Some(payload) => Message::Binary(encode_binary_frame(response, payload)?),
+        None => Message::Text(serde_json::to_string(response)?),
+    })
 }
 
 // --- WebSocket Server ---
 
-async fn start_websocket_server<R: Runtime>(app_handle: AppHandle<R>) {
-    let addr = SocketAddr::from(([127, 0, 0, 1], 31753));
-    let listener = match TcpListener::bind(&addr).await {
+/// Binds the WebSocket port: the configured one, then the next `fallbackPorts` ones,
+/// then any free port picked by the OS, so two games can run side by side
+fn bind_websocket_listener(config: &ServerConfig) -> anyhow::Result<std::net::TcpListener> {
+    let fallback_ports = if config.port == 0 {
+        0
+    } else {
+        config.fallback_ports
+    };
+    let candidates = (0..=fallback_ports)
+        .filter_map(|offset| config.port.checked_add(offset))
+        .chain(std::iter::once(0));
+
+    for port in candidates {
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        match std::net::TcpListener::bind(addr) {
+            Ok(listener) => {
+                // Required by tokio's from_std
+                listener.set_nonblocking(true)?;
+                return Ok(listener);
+            }
+            Err(e) => warn!("Failed to bind WebSocket server to {}: {}", addr, e),
+        }
+    }
+    Err(anyhow::anyhow!(
+        "No port available for the WebSocket server"
+    ))
+}
+
+async fn start_websocket_server<R: Runtime>(
+    listener: std::net::TcpListener,
+    app_handle: AppHandle<R>,
+    router: Arc<Router<R>>,
+) {
+    let listener = match TcpListener::from_std(listener) {
         Ok(l) => l,
         Err(e) => {
-            eprintln!("Failed to bind WebSocket server to {}: {}", addr, e);
+            error!("Failed to start WebSocket server: {}", e);
             return;
         }
     };
-    println!("WebSocket server running on ws://{}", addr);
+    if let Ok(addr) = listener.local_addr() {
+        info!("WebSocket server running on ws://{}", addr);
+    }
 
+    let shutdown = app_handle.state::<Shutdown>();
     loop {
-        match listener.accept().await {
-            Ok((stream, _)) => {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = shutdown.stopping() => {
+                info!("WebSocket server no longer accepts connections");
+                break;
+            }
+        };
+        match accepted {
+            Ok((stream, addr)) => {
                 let app_handle_clone = app_handle.clone();
-                tokio::spawn(async move {
-                    handle_websocket(stream, app_handle_clone).await;
-                });
+                let router_clone = router.clone();
+                let guard = shutdown.track_connection();
+                // `id` is recorded once the client is authenticated
+                let span = info_span!("connection", %addr, id = field::Empty);
+                tokio::spawn(
+                    async move {
+                        let _guard = guard;
+                        handle_websocket(stream, app_handle_clone, router_clone).await;
+                    }
+                    .instrument(span),
+                );
             }
             Err(e) => {
-                eprintln!("Failed to accept incoming connection: {}", e);
+                error!("Failed to accept incoming connection: {}", e);
                 tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
             }
         }
@@ -600,17 +508,171 @@ async fn start_websocket_server<R: Runtime>(app_handle: AppHandle<R>) {
 
 // --- Tauri Setup ---
 
+/// Creates the `main` window from `tauri.conf.json`, exposing `globals` to the game
+/// as `window.__PIPELAB__` before any of its scripts run
+fn create_main_window<R: Runtime>(app_handle: &AppHandle<R>, globals: Value) -> anyhow::Result<()> {
+    let window_config = app_handle
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|window| window.label == "main")
+        .ok_or_else(|| anyhow::anyhow!("No window labelled 'main' in tauri.conf.json"))?
+        .clone();
+    let script = format!(
+        "window.__PIPELAB__ = Object.assign(window.__PIPELAB__ || {{}}, {});",
+        globals
+    );
+    WebviewWindowBuilder::from_config(app_handle, &window_config)?
+        .initialization_script(&script)
+        .build()?;
+    Ok(())
+}
+
+/// Everything that must work for the game to run. An error here is shown to the
+/// player in the startup error window instead of the game.
+fn start<R: Runtime>(app_handle: &AppHandle<R>, router: Arc<Router<R>>) -> anyhow::Result<()> {
+    startup::check_frontend(app_handle)?;
+    startup::check_data_dir(app_handle)?;
+
+    let listener = bind_websocket_listener(&app_handle.state::<PipelabConfig>().server)?;
+    let port = listener.local_addr()?.port();
+    let token = AuthToken::generate()?;
+    create_main_window(
+        app_handle,
+        json!({ "port": port, "token": token.as_str(), "features": FEATURES }),
+    )?;
+    app_handle.manage(token);
+
+    serve(app_handle, router, listener);
+    Ok(())
+}
+
+/// Registers the state the WebSocket server and the handlers rely on, from the
+/// `plugins.pipelab` section of `config`, and the `pipelab_request` IPC command
+pub fn configure_builder<R: Runtime>(
+    builder: tauri::Builder<R>,
+    config: &tauri::Config,
+) -> tauri::Builder<R> {
+    let pipelab_config = PipelabConfig::load(config);
+    let origins = AllowedOrigins::new(config, &pipelab_config.allowed_origins);
+    let connections = Connections::new(&pipelab_config.session);
+
+    builder
+        .manage(pipelab_config)
+        .manage(origins)
+        .manage(connections)
+        .manage(PendingClose::default())
+        .manage(Shutdown::default())
+        .on_window_event(handlers::window::on_window_event)
+        .invoke_handler(tauri::generate_handler![ipc::pipelab_request])
+}
+
+/// Shares the router between the WebSocket server and [`ipc::pipelab_request`],
+/// and forwards events over IPC
+pub fn manage_router<R: Runtime>(app_handle: &AppHandle<R>, router: Router<R>) -> Arc<Router<R>> {
+    let router = Arc::new(router);
+    app_handle.manage(router.clone());
+    ipc::forward_events(app_handle);
+    router
+}
+
+/// Accepts clients on `listener` in the background. The app must be built with
+/// [`configure_builder`] and have an [`AuthToken`]; the listener must be non-blocking.
+///
+/// `run_with_routes` calls it once the main window is up. Tests call it on an app
+/// built with Tauri's mock runtime.
+pub fn serve<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    router: Arc<Router<R>>,
+    listener: std::net::TcpListener,
+) {
+    let app_handle = app_handle.clone();
+    async_runtime::spawn(async move {
+        start_websocket_server(listener, app_handle, router).await;
+    });
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    run_with_routes(|router| router)
+}
+
+/// Runs the app with extra routes registered on top of the built-in ones.
+///
+/// Routes added here replace built-in routes with the same URL, which lets a
+/// game answer its own messages without forking this crate. Handlers that take
+/// a body type get it deserialized and validated by the router. Bodies and
+/// replies derive `TS` and `JsonSchema` too, for the generated bindings:
+///
+/// ```no_run
+/// use schemars::JsonSchema;
+/// use serde::{Deserialize, Serialize};
+/// use tauri_lib::router::Request;
+/// use ts_rs::TS;
+///
+/// #[derive(Deserialize, TS, JsonSchema)]
+/// struct PingBody {
+///     message: String,
+/// }
+///
+/// #[derive(Serialize, TS, JsonSchema)]
+/// struct PingReply {
+///     pong: String,
+/// }
+///
+/// tauri_lib::run_with_routes(|router| {
+///     router
+///         .route("/my-game/ready", |_request: Request<tauri::Wry>| async {
+///             anyhow::Ok(serde_json::json!({ "ready": true }))
+///         })
+///         .route(
+///             "/my-game/ping",
+///             |_request: Request<tauri::Wry>, body: PingBody| async move {
+///                 anyhow::Ok(PingReply { pong: body.message })
+///             },
+///         )
+/// });
+/// ```
+pub fn run_with_routes<F>(extend: F)
+where
+    F: FnOnce(Router<Wry>) -> Router<Wry> + Send + 'static,
+{
+    // Lets anyhow capture backtraces for error replies, see ErrorBody::from_error
+    #[cfg(debug_assertions)]
+    if std::env::var_os("RUST_LIB_BACKTRACE").is_none() {
+        std::env::set_var("RUST_LIB_BACKTRACE", "1");
+    }
+
+    let context = tauri::generate_context!();
+    let log_file = logging::init(&LogConfig::load(context.config()));
+
+    configure_builder(tauri::Builder::default(), context.config())
         .setup(move |app| {
-            let app_handle = app.handle().clone();
-            async_runtime::spawn(async move {
-                start_websocket_server(app_handle).await;
-            });
+            let log_path = app
+                .path()
+                .app_log_dir()
+                .map_err(anyhow::Error::from)
+                .and_then(|dir| Ok(log_file.open(&dir)?));
+            match log_path {
+                Ok(path) => info!("Logging to {}", path.display()),
+                Err(e) => warn!("Failed to open the log file: {:#}", e),
+            }
+            // Added first, so it runs last and records the other hooks
+            app.state::<Shutdown>()
+                .add_hook("log file", move || async move {
+                    let mut log_file = log_file.as_ref();
+                    Ok(log_file.flush()?)
+                });
 
+            let router = manage_router(app.handle(), extend(handlers::register(Router::new())));
+            if let Err(e) = start(app.handle(), router) {
+                error!("Failed to start the game: {:#}", e);
+                startup::show_error_window(app.handle(), &e)?;
+            }
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(context)
+        .expect("error while building tauri application")
+        .run(shutdown::on_run_event);
 }
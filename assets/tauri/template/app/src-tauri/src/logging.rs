@@ -0,0 +1,222 @@
+use serde_json::Value;
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+use crate::config::{LogConfig, LogLevel};
+
+/// Name of the log file in the app log folder. Rotated files get `.1`, `.2`...
+pub const FILE_NAME: &str = "pipelab.log";
+
+/// Set once by [`init`], read when formatting payloads
+static CONFIG: OnceLock<LogConfig> = OnceLock::new();
+
+/// Installs the global subscriber, writing to stderr and to the returned [`LogFile`]
+/// once it is opened. Release builds on Windows have no console, so the file is
+/// the only place their logs end up.
+pub fn init(config: &LogConfig) -> Arc<LogFile> {
+    let _ = CONFIG.set(config.clone());
+    let level = level_filter(config);
+    let file = Arc::new(LogFile::new(config.max_file_size, config.max_files));
+
+    let result = tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(io::stderr)
+                .with_filter(level),
+        )
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(file.clone())
+                .with_filter(level),
+        )
+        .try_init();
+    if let Err(e) = result {
+        eprintln!("Logging is already set up: {}", e);
+    }
+    file
+}
+
+fn level_filter(config: &LogConfig) -> LevelFilter {
+    let level = match config.level {
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    };
+    if config.enable_extra_logging {
+        level.max(LevelFilter::DEBUG)
+    } else {
+        level
+    }
+}
+
+// --- Log file ---
+
+/// The log file, rotated once it grows past `max_size`: `pipelab.log` becomes
+/// `pipelab.log.1`, which becomes `pipelab.log.2`, and so on up to `max_files`.
+///
+/// Nothing is written until [`LogFile::open`], since the log folder comes from
+/// the app handle.
+pub struct LogFile {
+    max_size: u64,
+    max_files: usize,
+    current: Mutex<Option<OpenFile>>,
+}
+
+struct OpenFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn new(max_size: u64, max_files: usize) -> Self {
+        Self {
+            max_size,
+            max_files,
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Starts writing to `pipelab.log` in `dir`, after what it already holds
+    pub fn open(&self, dir: &Path) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(FILE_NAME);
+        let file = append(&path)?;
+        let size = file.metadata()?.len();
+        *self.current.lock().unwrap() = Some(OpenFile {
+            path: path.clone(),
+            file,
+            size,
+        });
+        Ok(path)
+    }
+
+    /// Shifts the rotated files by one, dropping the oldest, and starts a new file
+    fn rotate(&self, current: &mut OpenFile) -> io::Result<()> {
+        let rotated = |index: usize| {
+            let mut name = current.path.clone().into_os_string();
+            name.push(format!(".{}", index));
+            PathBuf::from(name)
+        };
+        if self.max_files == 0 {
+            current.file = File::create(&current.path)?;
+        } else {
+            let _ = std::fs::remove_file(rotated(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let _ = std::fs::rename(rotated(index), rotated(index + 1));
+            }
+            std::fs::rename(&current.path, rotated(1))?;
+            current.file = append(&current.path)?;
+        }
+        current.size = 0;
+        Ok(())
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Each event is written in one call, so files are only rotated between events
+impl Write for &LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut current = self.current.lock().unwrap();
+        let Some(current) = current.as_mut() else {
+            return Ok(buf.len());
+        };
+        if current.size > 0 && current.size + buf.len() as u64 > self.max_size {
+            self.rotate(current)?;
+        }
+        current.file.write_all(buf)?;
+        current.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current.lock().unwrap().as_mut() {
+            Some(current) => current.file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogFile {
+    type Writer = &'a LogFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+// --- Payloads ---
+
+/// Shows a message body in the logs with the `redactFields` hidden, cut to
+/// `maxPayloadLength` bytes
+pub struct Payload<'a>(pub &'a Value);
+
+impl fmt::Display for Payload<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        with_config(|config| {
+            let text = redact(self.0, &config.redact_fields).to_string();
+            f.write_str(&truncate(&text, config.max_payload_length))
+        })
+    }
+}
+
+/// Runs `f` with the config given to [`init`], or the default one
+fn with_config<T>(f: impl FnOnce(&LogConfig) -> T) -> T {
+    match CONFIG.get() {
+        Some(config) => f(config),
+        None => f(&LogConfig::default()),
+    }
+}
+
+/// Replaces the values of the given fields, at any depth
+fn redact(value: &Value, fields: &[String]) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| {
+                    let value = if fields.iter().any(|field| field == key) {
+                        match value {
+                            Value::String(text) => {
+                                Value::String(format!("[redacted, {} bytes]", text.len()))
+                            }
+                            _ => Value::String("[redacted]".to_string()),
+                        }
+                    } else {
+                        redact(value, fields)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|item| redact(item, fields)).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+/// Cuts text to `max_length` bytes, on a character boundary
+fn truncate(text: &str, max_length: usize) -> String {
+    if text.len() <= max_length {
+        return text.to_string();
+    }
+    let mut end = max_length;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} bytes)", &text[..end], text.len())
+}
@@ -0,0 +1,37 @@
+/// Origins the game's webview loads from, depending on the platform
+const APP_ORIGINS: &[&str] = &[
+    "tauri://localhost",
+    "http://tauri.localhost",
+    "https://tauri.localhost",
+];
+
+/// Origins allowed to open a WebSocket connection.
+///
+/// Browsers always send `Origin` in the handshake, so this keeps other tabs
+/// from reaching the runtime even before the token is checked. Clients that
+/// aren't browsers may leave it out, and only need the token.
+pub struct AllowedOrigins(Vec<String>);
+
+impl AllowedOrigins {
+    /// The app origins, the `devUrl` when running in dev mode (preview),
+    /// and the `allowedOrigins` of the pipelab config
+    pub fn new(config: &tauri::Config, extra: &[String]) -> Self {
+        let mut origins: Vec<String> = APP_ORIGINS.iter().map(|o| o.to_string()).collect();
+        if cfg!(dev) {
+            if let Some(dev_url) = &config.build.dev_url {
+                origins.push(dev_url.origin().ascii_serialization());
+            }
+        }
+        origins.extend(extra.iter().map(|origin| normalize(origin)));
+        Self(origins)
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        self.0.contains(&normalize(origin))
+    }
+}
+
+/// Origins are compared without case or trailing slash
+fn normalize(origin: &str) -> String {
+    origin.trim_end_matches('/').to_ascii_lowercase()
+}
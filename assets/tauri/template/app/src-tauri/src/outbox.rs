@@ -0,0 +1,217 @@
+use futures_util::{Sink, SinkExt};
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{mpsc, watch, Notify};
+use tokio_tungstenite::tungstenite::{
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+use tracing::warn;
+
+use crate::config::{ConnectionConfig, SlowClientPolicy};
+
+/// Sending side of a connection's outgoing queue.
+///
+/// Replies, events and pongs for a client all go through here and are written by a
+/// single task, [`write_messages`]. The queue is bounded: replies wait for room
+/// (backpressure on the handlers of that connection only), while events never wait
+/// and fall back to the configured [`SlowClientPolicy`] instead.
+#[derive(Clone)]
+pub struct Outbox {
+    queue: mpsc::Sender<Message>,
+    shared: Arc<Shared>,
+}
+
+/// Receiving side of the queue, owned by the writer task
+pub struct OutboxReceiver {
+    queue: mpsc::Receiver<Message>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    policy: SlowClientPolicy,
+    /// Events that didn't fit in the queue, keeping only the latest body per URL
+    coalesced: Mutex<Vec<(String, Arc<str>)>>,
+    /// Wakes the writer when `coalesced` changes
+    wake: Notify,
+    closed: watch::Sender<bool>,
+    /// Sent once the queue is flushed, when closed by [`Outbox::close_with`]
+    close_frame: Mutex<Option<CloseFrame<'static>>>,
+}
+
+/// Creates the queue of one connection
+pub fn outbox(config: &ConnectionConfig) -> (Outbox, OutboxReceiver) {
+    let (sender, receiver) = mpsc::channel(config.queue_size.max(1));
+    let shared = Arc::new(Shared {
+        policy: config.slow_client,
+        coalesced: Mutex::new(Vec::new()),
+        wake: Notify::new(),
+        closed: watch::Sender::new(false),
+        close_frame: Mutex::new(None),
+    });
+    (
+        Outbox {
+            queue: sender,
+            shared: shared.clone(),
+        },
+        OutboxReceiver {
+            queue: receiver,
+            shared,
+        },
+    )
+}
+
+impl Outbox {
+    /// Queues a message, waiting while the queue is full.
+    /// Returns `false` if the connection is closed.
+    pub async fn send(&self, message: Message) -> bool {
+        self.send_or_return(message).await.is_ok()
+    }
+
+    /// Like [`Self::send`], but hands the message back if the connection is closed
+    pub async fn send_or_return(&self, message: Message) -> Result<(), Message> {
+        if self.is_closed() {
+            return Err(message);
+        }
+        self.queue.send(message).await.map_err(|e| e.0)
+    }
+
+    /// Queues an event without waiting. Returns `false` if it was dropped.
+    pub fn push_event(&self, url: &str, event: Arc<str>) -> bool {
+        if self.is_closed() {
+            return false;
+        }
+        if self.shared.policy == SlowClientPolicy::Coalesce && self.coalesce(url, &event, false) {
+            // Events are already waiting, queue behind them to keep the order
+            return true;
+        }
+        match self.queue.try_send(Message::Text(event.to_string())) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+            Err(mpsc::error::TrySendError::Full(_)) => match self.shared.policy {
+                SlowClientPolicy::Disconnect => {
+                    warn!("Client is too slow, dropping the connection");
+                    self.close();
+                    false
+                }
+                SlowClientPolicy::Coalesce => self.coalesce(url, &event, true),
+            },
+        }
+    }
+
+    /// Adds an event to the coalesced ones. Unless `force` is set, only does so
+    /// when some are already waiting.
+    fn coalesce(&self, url: &str, event: &Arc<str>, force: bool) -> bool {
+        let mut coalesced = self.shared.coalesced.lock().unwrap();
+        if coalesced.is_empty() && !force {
+            return false;
+        }
+        match coalesced
+            .iter_mut()
+            .find(|(queued_url, _)| queued_url == url)
+        {
+            Some((_, queued)) => *queued = event.clone(),
+            None => coalesced.push((url.to_string(), event.clone())),
+        }
+        drop(coalesced);
+        self.shared.wake.notify_one();
+        true
+    }
+
+    /// Stops the writer, which sends a Close frame to the client
+    pub fn close(&self) {
+        self.shared.closed.send_replace(true);
+    }
+
+    /// Stops the writer once the messages already queued are written, and sends
+    /// a Close frame with `reason`
+    pub fn close_with(&self, code: CloseCode, reason: &str) {
+        *self.shared.close_frame.lock().unwrap() = Some(CloseFrame {
+            code,
+            reason: reason.to_string().into(),
+        });
+        self.close();
+    }
+
+    /// Whether both are the queue of the same connection
+    pub fn same_as(&self, other: &Outbox) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.shared.closed.borrow()
+    }
+
+    /// Resolves once the connection is closed, by [`Self::close`] or a write error
+    pub async fn closed(&self) {
+        let mut closed = self.shared.closed.subscribe();
+        let _ = closed.wait_for(|closed| *closed).await;
+    }
+}
+
+/// Writes queued messages to the client until the connection is closed.
+/// Returns the replies and events it didn't get to write.
+pub async fn write_messages<S>(mut sink: S, mut receiver: OutboxReceiver) -> Vec<Message>
+where
+    S: Sink<Message> + Unpin,
+    S::Error: Display,
+{
+    let shared = receiver.shared.clone();
+    let mut closed = shared.closed.subscribe();
+
+    'write: loop {
+        let message = tokio::select! {
+            biased;
+            _ = closed.wait_for(|closed| *closed) => break,
+            message = receiver.queue.recv() => match message {
+                Some(message) => Some(message),
+                None => break,
+            },
+            _ = shared.wake.notified() => None,
+        };
+        if let Some(message) = message {
+            if let Err(e) = sink.send(message).await {
+                warn!("Failed to write to the client: {}", e);
+                break;
+            }
+        }
+
+        // Coalesced events go out once the queue is drained, so they are never
+        // followed by older ones
+        if receiver.queue.is_empty() {
+            let events = std::mem::take(&mut *shared.coalesced.lock().unwrap());
+            for (_, event) in events {
+                if let Err(e) = sink.send(Message::Text(event.to_string())).await {
+                    warn!("Failed to write to the client: {}", e);
+                    break 'write;
+                }
+            }
+        }
+    }
+
+    shared.closed.send_replace(true);
+    // Later sends fail and keep their message, see `Outbox::send_or_return`
+    receiver.queue.close();
+    let mut unsent = Vec::new();
+    while let Ok(message) = receiver.queue.try_recv() {
+        if matches!(message, Message::Text(_) | Message::Binary(_)) {
+            unsent.push(message);
+        }
+    }
+
+    let close_frame = shared.close_frame.lock().unwrap().take();
+    if let Some(frame) = close_frame {
+        let mut queued = std::mem::take(&mut unsent).into_iter();
+        while let Some(message) = queued.next() {
+            if sink.send(message).await.is_err() {
+                unsent.extend(queued);
+                break;
+            }
+        }
+        let _ = sink.send(Message::Close(Some(frame))).await;
+    }
+    let _ = sink.close().await;
+    unsent
+}
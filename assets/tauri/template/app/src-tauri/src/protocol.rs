@@ -0,0 +1,160 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value; // Using Value for flexibility in body initially
+use ts_rs::TS;
+
+use crate::error::ErrorCode;
+
+// --- Message Structures ---
+
+/// Generic structure for incoming WebSocket messages
+#[derive(Deserialize, Debug, TS, JsonSchema)]
+#[ts(optional_fields)]
+pub struct IncomingMessage {
+    pub url: String,
+    #[serde(rename = "correlationId")] // Match JS naming
+    pub correlation_id: Option<String>, // Optional correlation ID
+    pub body: Option<Value>, // Use Option<Value> to handle cases where body might be missing
+    /// Raw bytes following the header of a binary frame
+    #[serde(skip)]
+    pub payload: Option<Vec<u8>>,
+}
+
+/// Generic structure for outgoing WebSocket responses
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct ResponseMessage<T> {
+    pub url: String,
+    #[serde(rename = "correlationId")]
+    pub correlation_id: Option<String>, // Echo back the correlation ID
+    pub body: T, // Generic body for success or error
+    /// Set on the final frame of a streamed response, see [`StreamMessage`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional, type = "number")]
+    pub seq: Option<u64>,
+    /// `true` on the final frame of a streamed response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub end: Option<bool>,
+    /// Raw bytes sent after the header, in a binary frame
+    #[serde(skip)]
+    pub payload: Option<Vec<u8>>,
+}
+
+// --- Streamed Responses ---
+
+/// Intermediate frame of a streamed response: `{ url, correlationId, seq, chunk }`
+/// or `{ url, correlationId, seq, progress }`. The final frame is the usual response
+/// with `seq` and `end: true`, and its body tells whether the request succeeded.
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct StreamMessage<T> {
+    pub url: String,
+    #[serde(rename = "correlationId")]
+    pub correlation_id: Option<String>,
+    /// Position of the frame in the stream, starting at 0
+    #[ts(type = "number")]
+    pub seq: u64,
+    #[serde(flatten)]
+    pub frame: T,
+}
+
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct Progress {
+    #[ts(type = "number")]
+    pub done: u64,
+    #[ts(type = "number | null")]
+    pub total: Option<u64>,
+}
+
+/// Message pushed by the server without a matching request, e.g. `/window/resized`
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct EventMessage<T> {
+    pub url: String,
+    pub body: T,
+}
+
+/// URL of the event telling a client about its session, sent before anything else
+pub const SESSION_URL: &str = "/session";
+
+/// Body of the `/session` event. A client that loses its connection can reconnect
+/// with `ws://127.0.0.1:{port}/?token=...&session={sessionId}`, or send `session`
+/// next to `token` in `/auth`, to get the events and replies it missed meanwhile.
+#[derive(Serialize, Debug, TS, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionEvent {
+    pub session_id: String,
+    /// `false` when a new session started, e.g. the previous one expired
+    pub resumed: bool,
+    /// Events the client missed but that weren't kept, over `session.maxMissedEvents`
+    #[ts(type = "number")]
+    pub dropped: usize,
+    /// Correlation IDs of the replies the client missed but that weren't kept, over
+    /// `session.maxMissedReplies`. These requests ran, but their result is lost.
+    pub dropped_replies: Vec<String>,
+}
+
+/// Success response body. The handler output is flattened next to `success`,
+/// the same way the Electron handlers reply with e.g. `{ success: true, size: 42 }`
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct SuccessBody<T> {
+    pub success: bool,
+    #[serde(flatten)]
+    pub data: T,
+}
+
+/// Error response body, built with [`ErrorBody::from_error`] for handler errors
+#[derive(Serialize, Debug, TS, JsonSchema)]
+pub struct ErrorBody {
+    pub success: bool,
+    pub error: String,
+    pub code: ErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub details: Option<Value>,
+    /// Only in debug builds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub backtrace: Option<String>,
+}
+
+// --- Binary Frames ---
+
+/// Optional protocol features, advertised to the game as `window.__PIPELAB__.features`
+/// and in the `/auth` reply. Clients that don't know them keep using JSON over the socket.
+///
+/// - `binary`: binary frames, see [`decode_binary_frame`]
+/// - `ipc`: the `pipelab_request` command, see [`crate::ipc`]
+/// - `session`: resuming after a reconnect, see [`SessionEvent`]
+pub const FEATURES: &[&str] = &["binary", "ipc", "session"];
+
+/// Reads a binary frame: a big-endian `u32` header length, the header as JSON
+/// (`{ url, correlationId, body }`, like a text message), then the raw payload.
+///
+/// A request sent this way is answered the same way when its reply carries bytes.
+pub fn decode_binary_frame(frame: &[u8]) -> anyhow::Result<IncomingMessage> {
+    let (length, rest) = frame
+        .split_first_chunk::<4>()
+        .ok_or_else(|| anyhow::anyhow!("Binary frame too short"))?;
+    let length = u32::from_be_bytes(*length) as usize;
+    if rest.len() < length {
+        return Err(anyhow::anyhow!(
+            "Binary frame header is {} bytes but only {} are left",
+            length,
+            rest.len()
+        ));
+    }
+    let (header, payload) = rest.split_at(length);
+    let mut message: IncomingMessage = serde_json::from_slice(header)?;
+    message.payload = Some(payload.to_vec());
+    Ok(message)
+}
+
+/// Builds a binary frame, see [`decode_binary_frame`]
+pub fn encode_binary_frame<T: Serialize>(header: &T, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let header = serde_json::to_vec(header)?;
+    let length = u32::try_from(header.len())?;
+    let mut frame = Vec::with_capacity(4 + header.len() + payload.len());
+    frame.extend_from_slice(&length.to_be_bytes());
+    frame.extend_from_slice(&header);
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
@@ -0,0 +1,516 @@
+use futures_util::future::BoxFuture;
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    marker::PhantomData,
+    sync::Arc,
+};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{debug, info_span, warn, Instrument};
+use ts_rs::TS;
+
+use crate::{
+    bindings::Bindings,
+    config::PipelabConfig,
+    connections::{ConnectionId, Connections},
+    error::{coded, CodedError, ErrorCode},
+    logging::Payload,
+    protocol::{ErrorBody, IncomingMessage, ResponseMessage, SuccessBody},
+    shutdown::Shutdown,
+    stream::ResponseStream,
+};
+
+// --- Requests ---
+
+/// A routed message, handed to a [`Handler`] together with the app it runs in
+pub struct Request<R: Runtime> {
+    pub url: String,
+    pub correlation_id: Option<String>,
+    /// The body as sent. Routes that declare a body type get it parsed as well.
+    pub body: Option<Value>,
+    pub app_handle: AppHandle<R>,
+    /// The WebSocket connection the message came from
+    pub connection_id: Option<ConnectionId>,
+    /// Raw bytes, when the message came in a binary frame
+    pub payload: Option<Vec<u8>>,
+    /// Set when the client asked for a streamed response with `stream: true` in the body.
+    /// Handlers that support it send chunks or progress through it before returning.
+    pub stream: Option<ResponseStream>,
+}
+
+/// Error for a required body field that is missing
+pub fn missing_field(name: &str) -> anyhow::Error {
+    invalid_body(name, format!("Missing '{}' field in body", name))
+}
+
+fn invalid_body(field: &str, message: String) -> anyhow::Error {
+    CodedError {
+        code: ErrorCode::InvalidBody,
+        message,
+        details: Some(json!({ "field": field })),
+    }
+    .into()
+}
+
+/// Deserializes the body of a request for a route that declares its body type.
+///
+/// A missing body reads as `{}`, so routes whose fields are all optional can be
+/// called without one. Errors are `INVALID_BODY` and name the field at fault in
+/// the message and in `details.field`. With `strictBodies` set in the config,
+/// fields the route doesn't know are rejected too, which catches a game plugin
+/// and a runtime that don't agree on a route.
+pub fn parse_body<B: DeserializeOwned, R: Runtime>(request: &Request<R>) -> anyhow::Result<B> {
+    let empty = Value::Object(Map::new());
+    let body = match &request.body {
+        None | Some(Value::Null) => &empty,
+        Some(body) if body.is_object() => body,
+        Some(_) => {
+            return Err(coded(
+                ErrorCode::InvalidBody,
+                format!("Invalid body for {}: expected an object", request.url),
+            ))
+        }
+    };
+    let mut unknown = Vec::new();
+    let body = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+        body,
+        &mut |path: serde_ignored::Path| unknown.push(path.to_string()),
+    ))
+    .map_err(|e| {
+        let path = e.path().to_string();
+        // Missing fields are reported on the object holding them
+        if let Some(name) = missing_field_name(e.inner()) {
+            let field = match path.as_str() {
+                "." => name,
+                parent => format!("{}.{}", parent, name),
+            };
+            return missing_field(&field);
+        }
+        let message = e.inner().to_string();
+        match path.as_str() {
+            "." => coded(
+                ErrorCode::InvalidBody,
+                format!("Invalid body for {}: {}", request.url, message),
+            ),
+            field => invalid_body(
+                field,
+                format!("Invalid '{}' field in body: {}", field, message),
+            ),
+        }
+    })?;
+
+    let strict = request
+        .app_handle
+        .try_state::<PipelabConfig>()
+        .is_some_and(|config| config.strict_bodies);
+    if let Some(field) = unknown.first().filter(|_| strict) {
+        return Err(invalid_body(
+            field,
+            format!("Unknown '{}' field in body", field),
+        ));
+    }
+    Ok(body)
+}
+
+/// Name of the field a deserialization error is about, when it is missing.
+///
+/// `serde_json::Error` doesn't expose it, only serde's `missing field` message,
+/// checked by the `parse_body` test in `tests/conformance.rs`.
+fn missing_field_name(error: &serde_json::Error) -> Option<String> {
+    error
+        .to_string()
+        .strip_prefix("missing field `")?
+        .strip_suffix('`')
+        .map(String::from)
+}
+
+// --- Handlers ---
+
+/// Something that can answer a routed message.
+///
+/// The returned value is flattened into the success body next to `success: true`,
+/// so it should serialize to an object (or to `null` for an empty reply). A `success`
+/// field in that object overrides the default, for routes that report a negative
+/// answer without it being an error. Errors are turned into an [`ErrorBody`] by the router.
+///
+/// Implemented for every `async fn(Request<R>) -> anyhow::Result<T>` where `T: IntoReply`,
+/// i.e. any `Serialize` type or a [`Binary`] reply, and for every
+/// `async fn(Request<R>, B) -> anyhow::Result<T>` where `B: Deserialize` is the body
+/// of the route, parsed by [`parse_body`] before the handler runs.
+/// `Args` only tells the two kinds apart.
+///
+/// Bodies and replies also derive `TS` and `JsonSchema`, so the router can declare
+/// the types of every route in the generated [`Bindings`].
+pub trait Handler<R: Runtime, Args>: Send + Sync + 'static {
+    /// Body of the route, `()` when it takes none
+    type Body: TS + JsonSchema + 'static;
+    /// Reply of the route, see [`IntoReply::Declared`]
+    type Output: TS + JsonSchema + 'static;
+
+    fn call(&self, request: Request<R>) -> BoxFuture<'static, anyhow::Result<Reply>>;
+}
+
+impl<R, F, Fut, T> Handler<R, ()> for F
+where
+    R: Runtime,
+    F: Fn(Request<R>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+    T: IntoReply,
+{
+    type Body = ();
+    type Output = T::Declared;
+
+    fn call(&self, request: Request<R>) -> BoxFuture<'static, anyhow::Result<Reply>> {
+        let future = (self)(request);
+        Box::pin(async move { future.await?.into_reply() })
+    }
+}
+
+impl<R, F, Fut, T, B> Handler<R, (B,)> for F
+where
+    R: Runtime,
+    F: Fn(Request<R>, B) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+    T: IntoReply,
+    B: DeserializeOwned + TS + JsonSchema + 'static,
+{
+    type Body = B;
+    type Output = T::Declared;
+
+    fn call(&self, request: Request<R>) -> BoxFuture<'static, anyhow::Result<Reply>> {
+        let body = match parse_body(&request) {
+            Ok(body) => body,
+            Err(e) => return Box::pin(async move { Err(e) }),
+        };
+        let future = (self)(request, body);
+        Box::pin(async move { future.await?.into_reply() })
+    }
+}
+
+/// A handler with its body type erased, as stored by the [`Router`]
+type BoxedHandler<R> =
+    Arc<dyn Fn(Request<R>) -> BoxFuture<'static, anyhow::Result<Reply>> + Send + Sync>;
+
+fn boxed<R: Runtime, Args: 'static>(handler: impl Handler<R, Args>) -> BoxedHandler<R> {
+    Arc::new(move |request| handler.call(request))
+}
+
+/// A handler's answer, before the router wraps it in a response
+pub struct Reply {
+    pub body: Value,
+    /// Raw bytes, and the body field holding them for JSON clients
+    pub binary: Option<(&'static str, Vec<u8>)>,
+}
+
+pub trait IntoReply {
+    /// Type of the reply in the generated bindings
+    type Declared: TS + JsonSchema + 'static;
+
+    fn into_reply(self) -> anyhow::Result<Reply>;
+}
+
+impl<T: Serialize + TS + JsonSchema + 'static> IntoReply for T {
+    type Declared = T;
+
+    fn into_reply(self) -> anyhow::Result<Reply> {
+        Ok(Reply {
+            body: serde_json::to_value(self)?,
+            binary: None,
+        })
+    }
+}
+
+/// A reply carrying raw bytes, e.g. a file's contents.
+///
+/// Requests sent in a binary frame get `data` as the payload of a binary frame.
+/// Requests sent as JSON get it as an array of numbers in the `field` of the body,
+/// the way the Electron runtime replies.
+pub struct Binary<T> {
+    pub meta: T,
+    pub field: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// A reply built by hand, declared as `T` in the generated bindings.
+///
+/// For routes that don't always reply the same type, e.g. a file read that
+/// replies `{ size }` when streamed.
+pub struct Declared<T> {
+    reply: Reply,
+    declared: PhantomData<fn() -> T>,
+}
+
+impl Reply {
+    /// Declares the reply as `T` in the generated bindings
+    pub fn declared<T>(self) -> Declared<T> {
+        Declared {
+            reply: self,
+            declared: PhantomData,
+        }
+    }
+}
+
+/// Declared as any JSON value, see [`Reply::declared`]
+impl IntoReply for Reply {
+    type Declared = Value;
+
+    fn into_reply(self) -> anyhow::Result<Reply> {
+        Ok(self)
+    }
+}
+
+impl<T: TS + JsonSchema + 'static> IntoReply for Declared<T> {
+    type Declared = T;
+
+    fn into_reply(self) -> anyhow::Result<Reply> {
+        Ok(self.reply)
+    }
+}
+
+/// Declared as any JSON value, see [`Reply::declared`]
+impl<T: Serialize> IntoReply for Binary<T> {
+    type Declared = Value;
+
+    fn into_reply(self) -> anyhow::Result<Reply> {
+        Ok(Reply {
+            body: serde_json::to_value(self.meta)?,
+            binary: Some((self.field, self.data)),
+        })
+    }
+}
+
+impl Reply {
+    /// Splits the reply into the response data and the payload of a binary frame
+    fn into_parts(self, binary_request: bool) -> (Value, Option<Vec<u8>>) {
+        match self.binary {
+            Some((_, data)) if binary_request => (self.body, Some(data)),
+            Some((field, data)) => {
+                let mut body = match self.body {
+                    Value::Object(body) => body,
+                    _ => Map::new(),
+                };
+                body.insert(field.to_string(), Value::from(data));
+                (Value::Object(body), None)
+            }
+            None => (self.body, None),
+        }
+    }
+}
+
+// --- Router ---
+
+/// Maps message URLs to handlers.
+///
+/// Routes are matched exactly first, then by the longest registered prefix
+/// (e.g. `/steam/` catches every Steam route).
+pub struct Router<R: Runtime> {
+    routes: HashMap<String, BoxedHandler<R>>,
+    /// Declares the body and reply types of each route in the bindings
+    types: BTreeMap<String, fn(&mut Bindings, &str)>,
+    prefixes: Vec<(String, BoxedHandler<R>)>,
+}
+
+impl<R: Runtime> Default for Router<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Runtime> Router<R> {
+    pub fn new() -> Self {
+        Self {
+            routes: HashMap::new(),
+            types: BTreeMap::new(),
+            prefixes: Vec::new(),
+        }
+    }
+
+    /// Registers a handler for an exact URL, replacing any previous one
+    pub fn route<Args: 'static, H: Handler<R, Args>>(
+        mut self,
+        url: impl Into<String>,
+        handler: H,
+    ) -> Self {
+        let url = url.into();
+        self.types
+            .insert(url.clone(), Bindings::route::<H::Body, H::Output>);
+        self.routes.insert(url, boxed(handler));
+        self
+    }
+
+    /// Registers a handler for every URL starting with `prefix`
+    pub fn prefix<Args: 'static>(
+        mut self,
+        prefix: impl Into<String>,
+        handler: impl Handler<R, Args>,
+    ) -> Self {
+        let prefix = prefix.into();
+        self.prefixes.retain(|(existing, _)| *existing != prefix);
+        self.prefixes.push((prefix, boxed(handler)));
+        // Longest prefix first so the most specific one wins
+        self.prefixes
+            .sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        self
+    }
+
+    /// URLs registered with [`Router::route`]
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.routes.keys().map(String::as_str)
+    }
+
+    /// Declares the body and reply of every route registered with [`Router::route`]
+    pub fn declare(&self, bindings: &mut Bindings) {
+        for (url, declare) in &self.types {
+            declare(bindings, url);
+        }
+    }
+
+    fn find(&self, url: &str) -> Option<BoxedHandler<R>> {
+        self.routes.get(url).cloned().or_else(|| {
+            self.prefixes
+                .iter()
+                .find(|(prefix, _)| url.starts_with(prefix.as_str()))
+                .map(|(_, handler)| handler.clone())
+        })
+    }
+
+    /// Runs the handler registered for the message and builds the response,
+    /// in a `request` span
+    pub async fn dispatch(
+        &self,
+        message: IncomingMessage,
+        app_handle: AppHandle<R>,
+        connection_id: Option<ConnectionId>,
+    ) -> ResponseMessage<Value> {
+        let span = info_span!(
+            "request",
+            url = %message.url,
+            correlation_id = message.correlation_id.as_deref(),
+        );
+        self.handle(message, app_handle, connection_id)
+            .instrument(span)
+            .await
+    }
+
+    async fn handle(
+        &self,
+        message: IncomingMessage,
+        app_handle: AppHandle<R>,
+        connection_id: Option<ConnectionId>,
+    ) -> ResponseMessage<Value> {
+        let body = message.body.as_ref().unwrap_or(&Value::Null);
+        debug!(body = %Payload(body), "Routing message");
+
+        let stream = self.stream_for(&message, &app_handle, connection_id);
+        let url = message.url;
+        let correlation_id = message.correlation_id;
+        let binary_request = message.payload.is_some();
+        let stopping = app_handle.state::<Shutdown>().is_stopping();
+
+        // Requests from a connection can be cancelled by their correlation ID
+        let tracked = match (connection_id, &correlation_id) {
+            (Some(id), Some(correlation_id)) if !stopping => {
+                let connections = app_handle.state::<Connections>();
+                connections
+                    .track(id, correlation_id)
+                    .map(|cancelled| (connections, id, correlation_id.as_str(), cancelled))
+            }
+            _ => None,
+        };
+
+        let result = match self.find(&url) {
+            _ if stopping => Err(coded(ErrorCode::ShuttingDown, "The app is shutting down")),
+            Some(handler) => {
+                let call = handler(Request {
+                    url: url.clone(),
+                    correlation_id: correlation_id.clone(),
+                    body: message.body,
+                    app_handle: app_handle.clone(),
+                    connection_id,
+                    payload: message.payload,
+                    stream: stream.clone(),
+                });
+                match tracked {
+                    // Dropping the handler's future aborts it
+                    Some((connections, id, correlation_id, cancelled)) => {
+                        let result = tokio::select! {
+                            result = call => result,
+                            _ = cancelled => Err(coded(ErrorCode::Cancelled, "Request cancelled")),
+                        };
+                        connections.untrack(id, correlation_id);
+                        result
+                    }
+                    None => call.await,
+                }
+            }
+            None => {
+                debug!("No handler for this URL");
+                Err(coded(
+                    ErrorCode::NotImplemented,
+                    format!("Unhandled URL: {}", url),
+                ))
+            }
+        };
+
+        let (body, payload) = result
+            .and_then(|reply| {
+                let (data, payload) = reply.into_parts(binary_request);
+                let body = serde_json::to_value(SuccessBody {
+                    success: true,
+                    data,
+                })?;
+                Ok((body, payload))
+            })
+            .unwrap_or_else(|e| {
+                warn!("Error handling message: {:#}", e);
+                (serde_json::json!(ErrorBody::from_error(&e)), None)
+            });
+
+        let seq = match &stream {
+            Some(stream) => Some(stream.finish().await),
+            None => None,
+        };
+
+        ResponseMessage {
+            url,
+            correlation_id,
+            body,
+            seq,
+            end: seq.map(|_| true),
+            payload,
+        }
+    }
+
+    /// Opens a stream on the client's connection if the message asks for one
+    fn stream_for(
+        &self,
+        message: &IncomingMessage,
+        app_handle: &AppHandle<R>,
+        connection_id: Option<ConnectionId>,
+    ) -> Option<ResponseStream> {
+        let wants_stream = message
+            .body
+            .as_ref()
+            .and_then(|body| body.get("stream"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        if !wants_stream {
+            return None;
+        }
+        let outbox = app_handle.state::<Connections>().outbox(connection_id?)?;
+        Some(ResponseStream::new(
+            outbox,
+            message.url.clone(),
+            message.correlation_id.clone(),
+            message.payload.is_some(),
+        ))
+    }
+}
+
+/// Serializes an [`ErrorBody`] carrying the given message
+pub fn error_body(code: ErrorCode, error: String) -> Value {
+    serde_json::json!(ErrorBody::new(code, error))
+}
@@ -0,0 +1,210 @@
+use futures_util::future::BoxFuture;
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tauri::{async_runtime, AppHandle, Manager, RunEvent, Runtime, RESTART_EXIT_CODE};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+use crate::{config::PipelabConfig, connections::Connections};
+
+/// Reason sent in the Close frame of every client when the app exits
+pub const CLOSE_REASON: &str = "App is exiting";
+
+/// How long clients have to acknowledge their Close frame, like the Electron
+/// runtime's wait on its HTTP server
+const CLOSE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Coordinates the exit of the app, so a save being written isn't cut off.
+///
+/// Runs once, on [`RunEvent::ExitRequested`] or `/exit`, in the order of the
+/// Electron runtime's `cleanup`:
+///
+/// 1. The server stops accepting connections, and new requests are refused
+/// 2. Requests in flight get `shutdown.timeoutMs` to finish
+/// 3. Clients get their pending replies, then a Close frame with [`CLOSE_REASON`]
+/// 4. The hooks added with [`Shutdown::add_hook`] run, and the app exits
+///
+/// Subsystems like Discord RPC or Steam tear themselves down in a hook.
+pub struct Shutdown {
+    stopping: watch::Sender<bool>,
+    /// Set once the steps ran, so the exit they end with goes through
+    done: AtomicBool,
+    connections: Tracker,
+    requests: Tracker,
+    hooks: Mutex<Vec<(String, Hook)>>,
+}
+
+type Hook = Box<dyn FnOnce() -> BoxFuture<'static, anyhow::Result<()>> + Send>;
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            stopping: watch::Sender::new(false),
+            done: AtomicBool::new(false),
+            connections: Tracker::default(),
+            requests: Tracker::default(),
+            hooks: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn is_stopping(&self) -> bool {
+        *self.stopping.borrow()
+    }
+
+    /// Resolves once the app starts shutting down
+    pub async fn stopping(&self) {
+        let mut stopping = self.stopping.subscribe();
+        let _ = stopping.wait_for(|stopping| *stopping).await;
+    }
+
+    /// Keeps the shutdown waiting for a connection until the guard is dropped
+    pub fn track_connection(&self) -> TaskGuard {
+        self.connections.enter()
+    }
+
+    /// Keeps the shutdown waiting for a request until the guard is dropped,
+    /// or `shutdown.timeoutMs` passed
+    pub fn track_request(&self) -> TaskGuard {
+        self.requests.enter()
+    }
+
+    /// Runs `hook` before the app exits, once clients are disconnected:
+    ///
+    /// ```no_run
+    /// # fn example(app_handle: &tauri::AppHandle) {
+    /// use tauri::Manager;
+    /// use tauri_lib::shutdown::Shutdown;
+    ///
+    /// app_handle.state::<Shutdown>().add_hook("discord", || async {
+    ///     // Clear the activity...
+    ///     anyhow::Ok(())
+    /// });
+    /// # }
+    /// ```
+    ///
+    /// Hooks run one at a time, the last added first, so a subsystem added after
+    /// another is torn down before it. Each gets `shutdown.timeoutMs`. A hook added
+    /// once the shutdown started doesn't run.
+    pub fn add_hook<F, Fut>(&self, name: impl Into<String>, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.hooks
+            .lock()
+            .unwrap()
+            .push((name.into(), Box::new(move || Box::pin(hook()))));
+    }
+
+    async fn run_hooks(&self, timeout: Duration) {
+        let hooks = std::mem::take(&mut *self.hooks.lock().unwrap());
+        for (name, hook) in hooks.into_iter().rev() {
+            match tokio::time::timeout(timeout, hook()).await {
+                Ok(Ok(())) => debug!("Shutdown hook '{}' done", name),
+                Ok(Err(e)) => warn!("Shutdown hook '{}' failed: {:#}", name, e),
+                Err(_) => warn!(
+                    "Shutdown hook '{}' still running after {}ms, skipping it",
+                    name,
+                    timeout.as_millis()
+                ),
+            }
+        }
+    }
+}
+
+/// Shuts down in the background, then exits with `code`. Does nothing if the app
+/// is already shutting down.
+pub fn exit<R: Runtime>(app_handle: &AppHandle<R>, code: i32) {
+    let app_handle = app_handle.clone();
+    async_runtime::spawn(async move {
+        if run(&app_handle).await {
+            app_handle
+                .state::<Shutdown>()
+                .done
+                .store(true, Ordering::Relaxed);
+            app_handle.exit(code);
+        }
+    });
+}
+
+/// Holds [`RunEvent::ExitRequested`] until the shutdown ran. Restarts can't be held.
+pub fn on_run_event<R: Runtime>(app_handle: &AppHandle<R>, event: RunEvent) {
+    if let RunEvent::ExitRequested { code, api, .. } = event {
+        if code == Some(RESTART_EXIT_CODE)
+            || app_handle.state::<Shutdown>().done.load(Ordering::Relaxed)
+        {
+            return;
+        }
+        api.prevent_exit();
+        exit(app_handle, code.unwrap_or(0));
+    }
+}
+
+/// Runs the steps of [`Shutdown`] without exiting. Returns `false` if the app was
+/// already shutting down.
+pub async fn run<R: Runtime>(app_handle: &AppHandle<R>) -> bool {
+    let shutdown = app_handle.state::<Shutdown>();
+    if shutdown.stopping.send_replace(true) {
+        return false;
+    }
+    info!("Shutting down");
+    let timeout = Duration::from_millis(app_handle.state::<PipelabConfig>().shutdown.timeout_ms);
+
+    if !shutdown.requests.idle(timeout).await {
+        warn!(
+            "Requests still running after {}ms, exiting anyway",
+            timeout.as_millis()
+        );
+    }
+
+    let clients = app_handle.state::<Connections>().close_all(CLOSE_REASON);
+    if !shutdown.connections.idle(CLOSE_TIMEOUT).await {
+        warn!("Clients did not close in time");
+    }
+    info!("Closed {} client(s)", clients);
+
+    shutdown.run_hooks(timeout).await;
+    true
+}
+
+// --- Task tracking ---
+
+/// Counts the tasks of one kind still running
+struct Tracker(watch::Sender<usize>);
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Self(watch::Sender::new(0))
+    }
+}
+
+impl Tracker {
+    fn enter(&self) -> TaskGuard {
+        self.0.send_modify(|count| *count += 1);
+        TaskGuard(self.0.clone())
+    }
+
+    /// Waits until no task is running. Returns `false` on timeout.
+    async fn idle(&self, timeout: Duration) -> bool {
+        let mut count = self.0.subscribe();
+        let idle = tokio::time::timeout(timeout, count.wait_for(|count| *count == 0)).await;
+        idle.is_ok()
+    }
+}
+
+/// A task tracked by [`Shutdown`], until dropped
+pub struct TaskGuard(watch::Sender<usize>);
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
@@ -0,0 +1,157 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tauri::{AppHandle, Runtime, WebviewUrl, WebviewWindowBuilder, WindowEvent};
+
+use crate::handlers::paths::resolve_path;
+
+/// Label of the window shown when the game can't start
+pub const ERROR_WINDOW_LABEL: &str = "startup-error";
+
+/// Navigating here from the error page quits the app
+const CLOSE_URL: &str = "pipelab://close";
+
+// --- Checks ---
+
+/// Makes sure the game can save its data, the way `/fs` writes to `userData`
+pub fn check_data_dir<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<()> {
+    let dir = resolve_path(app_handle, "userData")?;
+    let probe = dir.join(format!(".pipelab-write-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&probe, b""))
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|e| anyhow::anyhow!("The data folder {} is not writable: {}", dir.display(), e))
+}
+
+/// Makes sure the game files were bundled. In dev they are served from `devUrl` instead.
+pub fn check_frontend<R: Runtime>(app_handle: &AppHandle<R>) -> anyhow::Result<()> {
+    if cfg!(dev)
+        || app_handle
+            .asset_resolver()
+            .get("index.html".into())
+            .is_some()
+    {
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "The game files are missing (no index.html)"
+    ))
+}
+
+// --- Error window ---
+
+/// Shows why the game couldn't start, with a Close button that quits the app,
+/// like `createErrorWindow` in the Electron runtime. Closing the window any other
+/// way quits with the same exit code.
+pub fn show_error_window<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    error: &anyhow::Error,
+) -> anyhow::Result<()> {
+    let page = format!(
+        "data:text/html;base64,{}",
+        STANDARD.encode(error_page(&format!("{:#}", error)))
+    );
+
+    let exit_handle = app_handle.clone();
+    let builder = WebviewWindowBuilder::new(
+        app_handle,
+        ERROR_WINDOW_LABEL,
+        WebviewUrl::External(page.parse()?),
+    )
+    .on_navigation(move |url| {
+        if url.as_str().starts_with(CLOSE_URL) {
+            exit_handle.exit(1);
+            return false;
+        }
+        true
+    });
+    #[cfg(desktop)]
+    let builder = builder
+        .title("Game Startup Error")
+        .inner_size(600.0, 250.0)
+        .resizable(false)
+        .center();
+    let window = builder.build()?;
+
+    // Otherwise the app exits with 0 once its last window is gone
+    let exit_handle = app_handle.clone();
+    window.on_window_event(move |event| match event {
+        WindowEvent::CloseRequested { api, .. } => {
+            api.prevent_close();
+            exit_handle.exit(1);
+        }
+        WindowEvent::Destroyed => exit_handle.exit(1),
+        _ => {}
+    });
+    Ok(())
+}
+
+fn error_page(reason: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset="utf-8">
+    <title>Error</title>
+    <style>
+      html, body {{
+        margin: 0;
+        padding: 0;
+        height: 100%;
+        font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif;
+        background-color: #2c2f33;
+        color: #ffffff;
+        overflow: hidden;
+      }}
+      body {{
+        display: flex;
+        flex-direction: column;
+        justify-content: center;
+        align-items: center;
+        text-align: center;
+        padding: 1rem;
+        box-sizing: border-box;
+        gap: 1rem;
+      }}
+      h1 {{
+        font-weight: 400;
+        font-size: 1.2rem;
+        margin: 0;
+      }}
+      p {{
+        font-size: 0.9rem;
+        color: #b9bbbe;
+        margin: 0;
+        word-break: break-word;
+      }}
+      button {{
+        background-color: #404448;
+        color: #ffffff;
+        border: 1px solid #55585c;
+        border-radius: 6px;
+        padding: 0.75rem 1.5rem;
+        font-family: inherit;
+        font-size: 0.9rem;
+        cursor: pointer;
+      }}
+      button:hover {{
+        background-color: #4a4e52;
+      }}
+    </style>
+  </head>
+  <body>
+    <h1>Unable to start the game.</h1>
+    <p>{reason}</p>
+    <button onclick="window.location.href = '{close_url}'">Close</button>
+  </body>
+</html>"#,
+        reason = escape_html(reason),
+        close_url = CLOSE_URL,
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -0,0 +1,94 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    outbox::Outbox,
+    protocol::{encode_binary_frame, Progress, StreamMessage},
+};
+
+/// Sends the intermediate frames of a streamed response, see [`StreamMessage`].
+///
+/// Handlers get one in [`crate::router::Request::stream`] when the client asked for
+/// `stream: true`. Frames are numbered and queued in order; each send waits for room
+/// in the connection's queue, so a slow client slows the handler down instead of
+/// filling memory. Sending fails once the client is gone, which ends the handler.
+#[derive(Clone)]
+pub struct ResponseStream {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    outbox: Outbox,
+    url: String,
+    correlation_id: Option<String>,
+    /// The request came in a binary frame, so bytes can be sent raw
+    binary: bool,
+    /// Held while sending, so frames are queued in `seq` order
+    next_seq: Mutex<u64>,
+}
+
+impl ResponseStream {
+    pub fn new(outbox: Outbox, url: String, correlation_id: Option<String>, binary: bool) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                outbox,
+                url,
+                correlation_id,
+                binary,
+                next_seq: Mutex::new(0),
+            }),
+        }
+    }
+
+    /// Sends `{ chunk }`
+    pub async fn chunk<T: Serialize>(&self, chunk: T) -> anyhow::Result<()> {
+        self.send(json!({ "chunk": chunk }), None).await
+    }
+
+    /// Sends raw bytes: as the payload of a binary frame for binary clients,
+    /// otherwise as a base64 string in `chunk`
+    pub async fn bytes(&self, data: &[u8]) -> anyhow::Result<()> {
+        if self.inner.binary {
+            self.send(json!({}), Some(data)).await
+        } else {
+            self.chunk(BASE64_STANDARD.encode(data)).await
+        }
+    }
+
+    /// Sends `{ progress: { done, total } }`
+    pub async fn progress(&self, done: u64, total: Option<u64>) -> anyhow::Result<()> {
+        self.send(json!({ "progress": Progress { done, total } }), None)
+            .await
+    }
+
+    async fn send(&self, frame: serde_json::Value, payload: Option<&[u8]>) -> anyhow::Result<()> {
+        let mut next_seq = self.inner.next_seq.lock().await;
+        let message = StreamMessage {
+            url: self.inner.url.clone(),
+            correlation_id: self.inner.correlation_id.clone(),
+            seq: *next_seq,
+            frame,
+        };
+        let message = match payload {
+            Some(payload) => Message::Binary(encode_binary_frame(&message, payload)?),
+            None => Message::Text(serde_json::to_string(&message)?),
+        };
+        if !self.inner.outbox.send(message).await {
+            return Err(anyhow::anyhow!("Connection closed"));
+        }
+        *next_seq += 1;
+        Ok(())
+    }
+
+    /// Numbers the final frame. Nothing should be sent after it.
+    pub async fn finish(&self) -> u64 {
+        let mut next_seq = self.inner.next_seq.lock().await;
+        let seq = *next_seq;
+        *next_seq += 1;
+        seq
+    }
+}
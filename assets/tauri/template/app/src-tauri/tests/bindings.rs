@@ -0,0 +1,88 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, path::PathBuf};
+use tauri::Wry;
+use tauri_lib::{
+    bindings::Bindings,
+    handlers,
+    router::{Request, Router},
+};
+use ts_rs::TS;
+
+/// Compares a generated file with the checked-in one, or rewrites it when
+/// `UPDATE_BINDINGS` is set
+fn check(file: &str, generated: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("bindings")
+        .join(file);
+    if std::env::var_os("UPDATE_BINDINGS").is_some() {
+        std::fs::write(&path, generated).unwrap();
+        return;
+    }
+    let checked_in = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        checked_in.replace("\r\n", "\n") == generated,
+        "bindings/{} is out of date, regenerate it with `UPDATE_BINDINGS=1 cargo test --test bindings`",
+        file
+    );
+}
+
+#[test]
+fn typescript_is_up_to_date() {
+    check("pipelab.d.ts", &Bindings::new().typescript());
+}
+
+#[test]
+fn json_schema_is_up_to_date() {
+    let schema = serde_json::to_string_pretty(&Bindings::new().json_schema()).unwrap() + "\n";
+    check("pipelab.schema.json", &schema);
+}
+
+#[test]
+fn every_route_is_declared() {
+    let router = handlers::register(Router::<Wry>::new());
+    let registered: BTreeSet<&str> = router.urls().collect();
+    let bindings = Bindings::new();
+    let declared: BTreeSet<&str> = bindings.urls().collect();
+    assert_eq!(registered, declared);
+}
+
+#[derive(Deserialize, TS, JsonSchema)]
+struct PingBody {
+    message: String,
+}
+
+#[derive(Serialize, TS, JsonSchema)]
+struct PingReply {
+    pong: String,
+}
+
+#[test]
+fn routes_are_declared_with_their_handler_types() {
+    let router = handlers::register(Router::<Wry>::new())
+        .route(
+            "/my-game/ping",
+            |_request: Request<Wry>, body: PingBody| async move {
+                anyhow::Ok(PingReply { pong: body.message })
+            },
+        )
+        // Replacing a route replaces its types too
+        .route("/engine", |_request: Request<Wry>| async { anyhow::Ok(()) });
+    let bindings = Bindings::from_router(&router);
+
+    let typescript = bindings.typescript();
+    assert!(typescript.contains("export type PingBody = { message: string, };"));
+    assert!(typescript.contains(r#""/my-game/ping": { body: PingBody; reply: PingReply };"#));
+    assert!(typescript.contains(r#""/engine": { body: {}; reply: {} };"#));
+    assert!(typescript.contains(r#""/fs/exist": { body: PathBody; reply: ExistReply };"#));
+
+    let routes = &bindings.json_schema()["routes"];
+    assert_eq!(
+        routes["/my-game/ping"]["body"],
+        serde_json::json!({ "$ref": "#/$defs/PingBody" })
+    );
+    assert_eq!(
+        routes["/engine"]["reply"],
+        serde_json::json!({ "type": "null" })
+    );
+}
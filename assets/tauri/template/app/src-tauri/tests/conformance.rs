@@ -0,0 +1,850 @@
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tauri::{
+    ipc::{CallbackFn, InvokeBody},
+    test::{get_ipc_response, mock_builder, mock_context, noop_assets, MockRuntime, INVOKE_KEY},
+    webview::InvokeRequest,
+    App, AppHandle, Listener, Manager, WebviewWindow, WebviewWindowBuilder,
+};
+use tauri_lib::{
+    auth::AuthToken,
+    config::{ConnectionConfig, SessionConfig},
+    connections::Connections,
+    handlers, ipc, outbox,
+    protocol::{decode_binary_frame, encode_binary_frame, ErrorBody},
+    router::{self, Request, Router},
+    shutdown::{self, Shutdown},
+};
+use tokio::{net::TcpStream, sync::Notify};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{
+        self, client::IntoClientRequest, http::HeaderValue, protocol::frame::coding::CloseCode,
+        Message,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How the fixtures reach the handlers
+enum Transport {
+    WebSocket(Box<Client>),
+    /// `pipelab_request` from the main webview
+    Ipc(Box<WebviewWindow<MockRuntime>>),
+}
+
+#[tokio::test]
+async fn general() {
+    replay("general.json", websocket().await).await;
+    replay("general.json", ipc()).await;
+}
+
+#[tokio::test]
+async fn paths() {
+    replay("paths.json", websocket().await).await;
+    replay("paths.json", ipc()).await;
+}
+
+#[tokio::test]
+async fn run() {
+    replay("run.json", websocket().await).await;
+    replay("run.json", ipc()).await;
+}
+
+#[tokio::test]
+async fn fs() {
+    replay("fs.json", websocket().await).await;
+    replay("fs.json", ipc()).await;
+}
+
+#[tokio::test]
+async fn ipc_events() {
+    let Transport::Ipc(webview) = ipc() else {
+        unreachable!()
+    };
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    webview.listen(ipc::EVENT, move |event| {
+        let _ = sender.send(event.payload().to_string());
+    });
+    let connections = webview.state::<Connections>();
+
+    // Nothing goes out over IPC until the game sends a request
+    assert_eq!(connections.broadcast("/window/focus", json!({})), 0);
+    request(&webview, json!({ "url": "/engine" }));
+    assert_eq!(connections.broadcast("/window/blur", json!({})), 1);
+
+    let event: Value = serde_json::from_str(&receiver.recv().await.unwrap()).unwrap();
+    assert_eq!(event, json!({ "url": "/window/blur", "body": {} }));
+}
+
+#[tokio::test]
+async fn shutdown() {
+    let (app_handle, url) = serve_mock(builtin_routes());
+    let mut client = connect(&url).await.unwrap();
+    let ran = Arc::new(Mutex::new(Vec::new()));
+    for name in ["first", "second", "failing"] {
+        let ran = ran.clone();
+        app_handle
+            .state::<Shutdown>()
+            .add_hook(name, move || async move {
+                ran.lock().unwrap().push(name);
+                anyhow::ensure!(name != "failing", "Hook failed");
+                Ok(())
+            });
+    }
+
+    assert!(shutdown::run(&app_handle).await);
+    assert!(!shutdown::run(&app_handle).await);
+    // The last added runs first, and a failing hook doesn't stop the others
+    assert_eq!(*ran.lock().unwrap(), ["failing", "second", "first"]);
+
+    // Clients are told why they are disconnected
+    let close = loop {
+        match client.next().await {
+            Some(Ok(Message::Close(frame))) => break frame.unwrap(),
+            Some(Ok(_)) => continue,
+            other => panic!("Expected a Close frame, got {:?}", other),
+        }
+    };
+    assert_eq!(close.code, CloseCode::Away);
+    assert_eq!(close.reason, shutdown::CLOSE_REASON);
+
+    // No new connection or request gets through
+    assert!(connect(&url).await.is_err());
+    let router = app_handle.state::<Arc<Router<MockRuntime>>>();
+    let message = serde_json::from_value(json!({ "url": "/engine" })).unwrap();
+    let response = router.dispatch(message, app_handle.clone(), None).await;
+    assert_eq!(response.body["code"], "SHUTTING_DOWN");
+}
+
+#[tokio::test]
+async fn session_resume() {
+    let started = Arc::new(Notify::new());
+    let release = Arc::new(Notify::new());
+    let (app_handle, url) = serve_mock(builtin_routes().route("/test/wait", {
+        let (started, release) = (started.clone(), release.clone());
+        move |_request: Request<MockRuntime>| {
+            let (started, release) = (started.clone(), release.clone());
+            async move {
+                started.notify_one();
+                release.notified().await;
+                anyhow::Ok(json!({ "waited": true }))
+            }
+        }
+    }));
+
+    let mut client = connect(&url).await.unwrap();
+    let session = next_json(&mut client).await;
+    assert_eq!(session["url"], "/session");
+    assert_eq!(session["body"]["resumed"], false);
+    let session_id = session["body"]["sessionId"].as_str().unwrap().to_string();
+
+    // A request that finishes and an event sent while the client is away...
+    let request = json!({ "url": "/test/wait", "correlationId": "wait" });
+    client
+        .send(Message::Text(request.to_string()))
+        .await
+        .unwrap();
+    started.notified().await;
+    client.close(None).await.unwrap();
+    let connections = app_handle.state::<Connections>();
+    while !connections.is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(
+        connections.broadcast("/test/event", json!({ "missed": true })),
+        1
+    );
+    release.notify_one();
+
+    // ...reach it in order once it resumes its session
+    let mut client = connect(&format!("{}&session={}", url, session_id))
+        .await
+        .unwrap();
+    let session = next_json(&mut client).await;
+    assert_eq!(
+        session["body"],
+        json!({ "sessionId": session_id, "resumed": true, "dropped": 0, "droppedReplies": [] })
+    );
+    assert_eq!(
+        next_json(&mut client).await,
+        json!({ "url": "/test/event", "body": { "missed": true } })
+    );
+    let reply = next_json(&mut client).await;
+    assert_eq!(reply["correlationId"], "wait");
+    assert_eq!(reply["body"], json!({ "success": true, "waited": true }));
+
+    // A session that doesn't exist is replaced by a new one
+    let mut client = connect(&format!("{}&session=unknown", url)).await.unwrap();
+    let session = next_json(&mut client).await;
+    assert_eq!(session["body"]["resumed"], false);
+    assert_ne!(session["body"]["sessionId"], session_id.as_str());
+}
+
+#[tokio::test]
+async fn session_limits() {
+    let connections = Connections::new(&SessionConfig {
+        max_missed_events: 1,
+        max_missed_replies: 1,
+        ..Default::default()
+    });
+    let addr = "127.0.0.1:1".parse().unwrap();
+    let (outbox, _receiver) = outbox::outbox(&ConnectionConfig::default());
+    let (id, session) = connections.register(addr, outbox.clone()).unwrap();
+    connections.replay(id, &outbox).await;
+    connections.detach(id, &outbox, Vec::new());
+
+    // Only the latest event and reply are kept while the client is away...
+    for correlation_id in ["first", "second"] {
+        let reply = Message::Text(correlation_id.into());
+        assert!(
+            connections
+                .reply(id, Some(correlation_id.into()), reply)
+                .await
+        );
+    }
+    for n in 0..3 {
+        connections.broadcast("/test/event", json!({ "n": n }));
+    }
+
+    // ...and it is told about the others when it comes back
+    let (outbox, receiver) = outbox::outbox(&ConnectionConfig::default());
+    let (_, resumed) = connections
+        .resume(&session.session_id, addr, outbox.clone())
+        .unwrap();
+    assert_eq!(resumed.dropped, 2);
+    assert_eq!(resumed.dropped_replies, ["first"]);
+    assert_eq!(connections.replay(id, &outbox).await, 2);
+
+    outbox.close();
+    let replayed = outbox::write_messages(futures_util::sink::drain(), receiver).await;
+    let replayed: Vec<Value> = replayed
+        .into_iter()
+        .map(|message| match message {
+            Message::Text(text) => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+            other => panic!("Expected a text message, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        replayed,
+        [
+            json!("second"),
+            json!({ "url": "/test/event", "body": { "n": 2 } })
+        ]
+    );
+}
+
+#[tokio::test]
+async fn cancel() {
+    let started = Arc::new(Notify::new());
+    let aborted = Arc::new(Notify::new());
+    let (_, url) = serve_mock(wait_route(builtin_routes(), &started, &aborted));
+    let mut client = connect(&url).await.unwrap();
+    let send = |message: Value| Message::Text(message.to_string());
+
+    // A request in flight is stopped, and replies that it was cancelled
+    client
+        .send(send(
+            json!({ "url": "/test/wait", "correlationId": "wait" }),
+        ))
+        .await
+        .unwrap();
+    started.notified().await;
+    let cancel =
+        json!({ "url": "/cancel", "correlationId": "cancel", "body": { "correlationId": "wait" } });
+    client.send(send(cancel.clone())).await.unwrap();
+    // Both replies come, in any order
+    let mut replies = std::collections::HashMap::new();
+    while replies.len() < 2 {
+        let reply = next_json(&mut client).await;
+        if let Some(correlation_id) = reply["correlationId"].as_str() {
+            replies.insert(correlation_id.to_string(), reply["body"].clone());
+        }
+    }
+    assert_eq!(replies["wait"]["success"], false);
+    assert_eq!(replies["wait"]["code"], "CANCELLED");
+    assert_eq!(replies["cancel"], json!({ "success": true }));
+    tokio::time::timeout(Duration::from_secs(5), aborted.notified())
+        .await
+        .expect("The handler kept running");
+
+    // Requests that aren't in flight can't be
+    for correlation_id in ["wait", "unknown"] {
+        let cancel = json!({ "url": "/cancel", "correlationId": "cancel", "body": { "correlationId": correlation_id } });
+        client.send(send(cancel)).await.unwrap();
+        let (_, reply) = receive(&mut client, "cancel").await;
+        assert_eq!(reply["body"]["success"], false);
+        assert_eq!(reply["body"]["code"], "NOT_FOUND");
+    }
+}
+
+#[tokio::test]
+async fn cancel_on_expiry() {
+    let started = Arc::new(Notify::new());
+    let aborted = Arc::new(Notify::new());
+    let (_, url) = serve_mock(wait_route(builtin_routes(), &started, &aborted));
+    let mut client = connect(&url).await.unwrap();
+    let request = json!({ "url": "/test/wait", "correlationId": "wait" });
+    client
+        .send(Message::Text(request.to_string()))
+        .await
+        .unwrap();
+    started.notified().await;
+    client.close(None).await.unwrap();
+    let closed = Instant::now();
+
+    // Requests keep running while the session can be resumed, and are cancelled
+    // once it expires
+    let grace_period = Duration::from_millis(SessionConfig::default().grace_period_ms);
+    tokio::time::timeout(grace_period + Duration::from_secs(5), aborted.notified())
+        .await
+        .expect("The handler kept running after the session expired");
+    assert!(
+        closed.elapsed() >= grace_period,
+        "The handler was cancelled after {:?}, before the session expired",
+        closed.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn binary_frames() {
+    let (_, url) = serve_mock(builtin_routes());
+    let mut client = connect(&url).await.unwrap();
+    let tmp = temp_dir();
+    let path = slash(&tmp.join("bytes.bin"));
+    let bytes = [0, 1, 2, 255, b'{', 0];
+
+    let header = json!({ "url": "/test", "correlationId": "1", "body": { "a": 1 } });
+    let frame = encode_binary_frame(&header, &bytes).unwrap();
+    // A big-endian u32 header length, the JSON header, then the payload
+    let (length, rest) = frame.split_at(4);
+    let (json, payload) = rest.split_at(u32::from_be_bytes(length.try_into().unwrap()) as usize);
+    assert_eq!(serde_json::from_slice::<Value>(json).unwrap(), header);
+    assert_eq!(payload, bytes);
+    let message = decode_binary_frame(&frame).unwrap();
+    assert_eq!(message.url, "/test");
+    assert_eq!(message.correlation_id.as_deref(), Some("1"));
+    assert_eq!(message.body, Some(json!({ "a": 1 })));
+    assert_eq!(message.payload.as_deref(), Some(&bytes[..]));
+
+    // The payload is written as is...
+    let header =
+        json!({ "url": "/fs/file/write", "correlationId": "write", "body": { "path": path } });
+    let frame = encode_binary_frame(&header, &bytes).unwrap();
+    client.send(Message::Binary(frame)).await.unwrap();
+    let (_, reply) = receive(&mut client, "write").await;
+    assert_eq!(reply["body"], json!({ "success": true }));
+    assert_eq!(std::fs::read(tmp.join("bytes.bin")).unwrap(), bytes);
+
+    // ...and read back in a binary frame
+    let header =
+        json!({ "url": "/fs/file/read/binary", "correlationId": "read", "body": { "path": path } });
+    let frame = encode_binary_frame(&header, &[]).unwrap();
+    client.send(Message::Binary(frame)).await.unwrap();
+    let reply = loop {
+        match client.next().await {
+            Some(Ok(Message::Binary(frame))) => break decode_binary_frame(&frame).unwrap(),
+            Some(Ok(Message::Text(_))) => continue,
+            other => panic!("Expected a binary frame, got {:?}", other),
+        }
+    };
+    assert_eq!(reply.correlation_id.as_deref(), Some("read"));
+    assert_eq!(reply.body, Some(json!({ "success": true })));
+    assert_eq!(reply.payload.as_deref(), Some(&bytes[..]));
+
+    // Malformed frames get an error reply, and the connection goes on
+    let mut too_long = 100u32.to_be_bytes().to_vec();
+    too_long.extend_from_slice(b"{}");
+    let mut not_json = 5u32.to_be_bytes().to_vec();
+    not_json.extend_from_slice(b"{url:payload");
+    for (frame, error) in [
+        (vec![0, 0], "Binary frame too short"),
+        (
+            too_long,
+            "Binary frame header is 100 bytes but only 2 are left",
+        ),
+        (not_json, "key must be a string"),
+    ] {
+        client.send(Message::Binary(frame)).await.unwrap();
+        let reply = next_json(&mut client).await;
+        assert_eq!(reply["body"]["code"], "INVALID_BODY");
+        let message = reply["body"]["error"].as_str().unwrap();
+        assert!(
+            message.starts_with("Invalid binary frame: ") && message.contains(error),
+            "Unexpected error: {}",
+            message
+        );
+    }
+    let request = json!({ "url": "/engine", "correlationId": "engine" });
+    client
+        .send(Message::Text(request.to_string()))
+        .await
+        .unwrap();
+    let (_, reply) = receive(&mut client, "engine").await;
+    assert_eq!(reply["body"]["success"], true);
+    let _ = std::fs::remove_dir_all(&tmp);
+}
+
+#[tokio::test]
+async fn parse_body() {
+    // Only read through the errors
+    #[allow(dead_code)]
+    #[derive(Deserialize, Debug)]
+    struct Body {
+        name: String,
+        inner: Inner,
+    }
+    #[allow(dead_code)]
+    #[derive(Deserialize, Debug)]
+    struct Inner {
+        size: u32,
+    }
+
+    let app = mock_app(builtin_routes(), json!({ "strictBodies": true }));
+    let parse = |body: Option<Value>| {
+        let request = Request {
+            url: "/test".into(),
+            correlation_id: None,
+            body,
+            app_handle: app.handle().clone(),
+            connection_id: None,
+            payload: None,
+            stream: None,
+        };
+        let error = router::parse_body::<Body, _>(&request).unwrap_err();
+        serde_json::to_value(ErrorBody::from_error(&error)).unwrap()
+    };
+
+    let cases = [
+        (None, "Missing 'name' field in body", Some("name")),
+        (
+            Some(json!({ "name": "a", "inner": {} })),
+            "Missing 'inner.size' field in body",
+            Some("inner.size"),
+        ),
+        (
+            Some(json!({ "name": "a", "inner": { "size": "big" } })),
+            "Invalid 'inner.size' field in body: invalid type: string \"big\", expected u32",
+            Some("inner.size"),
+        ),
+        (
+            Some(json!({ "name": "a", "inner": { "size": 1, "color": "red" } })),
+            "Unknown 'inner.color' field in body",
+            Some("inner.color"),
+        ),
+        (
+            Some(json!(["a"])),
+            "Invalid body for /test: expected an object",
+            None,
+        ),
+    ];
+    for (body, error, field) in cases {
+        let expected = match field {
+            Some(field) => {
+                json!({ "error": error, "code": "INVALID_BODY", "details": { "field": field } })
+            }
+            None => json!({ "error": error, "code": "INVALID_BODY" }),
+        };
+        assert_matches(&expected, &parse(body), "parse_body", error);
+    }
+}
+
+#[tokio::test]
+async fn auth() {
+    let (_, url) = serve_mock_with(
+        builtin_routes(),
+        json!({ "connection": { "authTimeoutMs": 200 } }),
+    );
+    let (base, token) = url.split_once("?token=").unwrap();
+    let auth = |token: &str| {
+        let message =
+            json!({ "url": "/auth", "correlationId": "auth", "body": { "token": token } });
+        Message::Text(message.to_string())
+    };
+
+    // A wrong token in the handshake is refused
+    match connect(&format!("{}?token=wrong", base)).await {
+        Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), 401),
+        other => panic!("Expected a 401, got {:?}", other.map(|_| ())),
+    }
+
+    // Without one, the first message must be `/auth` with the right token...
+    let mut client = connect(base).await.unwrap();
+    client.send(auth("wrong")).await.unwrap();
+    let reply = next_json(&mut client).await;
+    assert_eq!(reply["correlationId"], "auth");
+    assert_eq!(reply["body"]["success"], false);
+    assert_eq!(reply["body"]["code"], "UNAUTHORIZED");
+    assert_closed(&mut client).await;
+
+    let mut client = connect(base).await.unwrap();
+    let request = json!({ "url": "/engine", "correlationId": "engine" });
+    client
+        .send(Message::Text(request.to_string()))
+        .await
+        .unwrap();
+    assert_eq!(next_json(&mut client).await["body"]["code"], "UNAUTHORIZED");
+    assert_closed(&mut client).await;
+
+    // ...sent in time
+    let connected = Instant::now();
+    let mut client = connect(base).await.unwrap();
+    assert_closed(&mut client).await;
+    assert!(connected.elapsed() >= Duration::from_millis(200));
+
+    // Once authenticated, requests go through
+    let mut client = connect(base).await.unwrap();
+    client.send(auth(token)).await.unwrap();
+    let reply = next_json(&mut client).await;
+    assert_eq!(reply["correlationId"], "auth");
+    assert_eq!(reply["body"]["success"], true);
+    client
+        .send(Message::Text(request.to_string()))
+        .await
+        .unwrap();
+    let (_, reply) = receive(&mut client, "engine").await;
+    assert_eq!(reply["body"], json!({ "success": true, "engine": "tauri" }));
+}
+
+#[tokio::test]
+async fn origins() {
+    let (_, url) = serve_mock_with(
+        builtin_routes(),
+        json!({ "allowedOrigins": ["http://localhost:8080/"] }),
+    );
+
+    // Other web pages are refused, even with the token
+    match connect_from(&url, Some("https://example.com")).await {
+        Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), 403),
+        other => panic!("Expected a 403, got {:?}", other.map(|_| ())),
+    }
+
+    // The app, the configured origins and clients that aren't browsers get through
+    for origin in [
+        Some("tauri://localhost"),
+        Some("http://tauri.localhost"),
+        Some("HTTP://localhost:8080"),
+        None,
+    ] {
+        let mut client = connect_from(&url, origin).await.unwrap();
+        assert_eq!(next_json(&mut client).await["url"], "/session");
+    }
+}
+
+/// Replays a fixture of `tests/fixtures` on a new app running on Tauri's mock runtime,
+/// in a new temp folder.
+///
+/// Each fixture is a list of cases run in order on one connection:
+///
+/// ```json
+/// { "name": "...", "request": { "url": "...", "body": {} }, "response": {}, "frames": [] }
+/// ```
+///
+/// `response` is matched against the body of the final reply, and `frames`, when
+/// present, against the streamed frames before it. IPC doesn't stream, so cases
+/// with `frames` only run over WebSocket. Objects match when every expected
+/// key matches, so optional extras like `details` can be left out; arrays and values
+/// must be equal. These placeholders stand for values that change between runs:
+///
+/// - `{tmp}` inside a string: a fresh folder, with forward slashes
+/// - `{cargo}` inside a string: the `cargo` running the tests
+/// - `"{string}"`, `"{number}"`, `"{any}"`: any value of that kind
+async fn replay(fixture: &str, mut transport: Transport) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(fixture);
+    let cases: Vec<Value> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap())
+        .unwrap_or_else(|e| panic!("Invalid fixture {}: {}", fixture, e));
+
+    let tmp = temp_dir();
+    for (index, case) in cases.iter().enumerate() {
+        let case = substitute(case, &tmp);
+        let name = case["name"].as_str().unwrap_or("unnamed");
+        match &mut transport {
+            Transport::WebSocket(client) => {
+                let correlation_id = format!("{}-{}", fixture, index);
+                let mut message = case["request"].clone();
+                message["correlationId"] = json!(correlation_id);
+                client
+                    .send(Message::Text(message.to_string()))
+                    .await
+                    .unwrap();
+
+                let (frames, response) = receive(client, &correlation_id).await;
+                assert_eq!(
+                    response["url"], case["request"]["url"],
+                    "{} > {}: wrong url",
+                    fixture, name
+                );
+                assert_matches(&case["response"], &response["body"], fixture, name);
+                if let Some(expected) = case.get("frames") {
+                    assert_matches(expected, &Value::Array(frames), fixture, name);
+                }
+            }
+            Transport::Ipc(_) if case.get("frames").is_some() => {}
+            Transport::Ipc(webview) => {
+                let webview = webview.clone();
+                let message = case["request"].clone();
+                let body = tokio::task::spawn_blocking(move || request(&webview, message))
+                    .await
+                    .unwrap();
+                assert_matches(&case["response"], &body, fixture, name);
+            }
+        }
+    }
+    let _ = std::fs::remove_dir_all(&tmp);
+}
+
+fn builtin_routes() -> Router<MockRuntime> {
+    handlers::register(Router::new())
+}
+
+/// Tells when a handler's future is dropped
+struct Aborted(Arc<Notify>);
+
+impl Drop for Aborted {
+    fn drop(&mut self) {
+        self.0.notify_one();
+    }
+}
+
+/// Adds `/test/wait`, which never answers. `started` is notified once it runs,
+/// `aborted` once it is dropped.
+fn wait_route(
+    router: Router<MockRuntime>,
+    started: &Arc<Notify>,
+    aborted: &Arc<Notify>,
+) -> Router<MockRuntime> {
+    let (started, aborted) = (started.clone(), aborted.clone());
+    router.route("/test/wait", move |_request: Request<MockRuntime>| {
+        let (started, aborted) = (started.clone(), Aborted(aborted.clone()));
+        async move {
+            let _aborted = aborted;
+            started.notify_one();
+            std::future::pending::<()>().await;
+            anyhow::Ok(())
+        }
+    })
+}
+
+/// Builds an app on the mock runtime, answering with `router`, with `pipelab`
+/// as the `plugins.pipelab` config
+fn mock_app(router: Router<MockRuntime>, pipelab: Value) -> App<MockRuntime> {
+    let context = mock_context(noop_assets());
+    let mut config = context.config().clone();
+    config.plugins.0.insert("pipelab".into(), pipelab);
+    let app = tauri_lib::configure_builder(mock_builder(), &config)
+        .build(context)
+        .unwrap();
+    tauri_lib::manage_router(app.handle(), router);
+    app
+}
+
+/// Starts the server on a mock app and connects to it like the game's webview
+async fn websocket() -> Transport {
+    let (_, url) = serve_mock(builtin_routes());
+    Transport::WebSocket(Box::new(connect(&url).await.unwrap()))
+}
+
+/// Starts the server on a mock app, returning the URL to connect to
+fn serve_mock(router: Router<MockRuntime>) -> (AppHandle<MockRuntime>, String) {
+    serve_mock_with(router, json!({}))
+}
+
+/// Like [`serve_mock`], with `pipelab` as the `plugins.pipelab` config
+fn serve_mock_with(
+    router: Router<MockRuntime>,
+    pipelab: Value,
+) -> (AppHandle<MockRuntime>, String) {
+    let app = mock_app(router, pipelab);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let token = AuthToken::generate().unwrap();
+    let url = format!(
+        "ws://{}/?token={}",
+        listener.local_addr().unwrap(),
+        token.as_str()
+    );
+    app.manage(token);
+    let router = app.state::<Arc<Router<MockRuntime>>>().inner().clone();
+    tauri_lib::serve(app.handle(), router, listener);
+    let app_handle = app.handle().clone();
+    // Keeps the app and its state alive for the rest of the test process
+    std::mem::forget(app);
+    (app_handle, url)
+}
+
+async fn connect(url: &str) -> Result<Client, tungstenite::Error> {
+    connect_from(url, Some("tauri://localhost")).await
+}
+
+/// Connects with `origin` as the `Origin` header, or without one
+async fn connect_from(url: &str, origin: Option<&str>) -> Result<Client, tungstenite::Error> {
+    let mut request = url.into_client_request().unwrap();
+    if let Some(origin) = origin {
+        request
+            .headers_mut()
+            .insert("Origin", HeaderValue::from_str(origin).unwrap());
+    }
+    connect_async(request).await.map(|(client, _)| client)
+}
+
+/// Opens the main webview of a mock app, to call `pipelab_request` from
+fn ipc() -> Transport {
+    let app = mock_app(builtin_routes(), json!({}));
+    let webview = WebviewWindowBuilder::new(&app, "main", Default::default())
+        .build()
+        .unwrap();
+    // Keeps the app and its state alive for the rest of the test process
+    std::mem::forget(app);
+    Transport::Ipc(Box::new(webview))
+}
+
+/// Calls `pipelab_request` with `{ url, body }`, returning the reply body
+fn request(webview: &WebviewWindow<MockRuntime>, message: Value) -> Value {
+    let response = get_ipc_response(
+        webview,
+        InvokeRequest {
+            cmd: "pipelab_request".into(),
+            callback: CallbackFn(0),
+            error: CallbackFn(1),
+            url: "tauri://localhost".parse().unwrap(),
+            body: InvokeBody::Json(json!({ "url": message["url"], "body": message["body"] })),
+            headers: Default::default(),
+            invoke_key: INVOKE_KEY.to_string(),
+        },
+    );
+    response.unwrap().deserialize().unwrap()
+}
+
+/// Reads the next text message
+async fn next_json(client: &mut Client) -> Value {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(30), client.next())
+            .await
+            .expect("No message in time")
+            .expect("Connection closed")
+            .unwrap();
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+/// Waits for the server to close the connection, failing on any text message
+async fn assert_closed(client: &mut Client) {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("Connection not closed in time");
+        match message {
+            Some(Ok(Message::Text(text))) => {
+                panic!("Expected the connection to close, got {}", text)
+            }
+            Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+            Some(Ok(_)) => continue,
+        }
+    }
+}
+
+/// Collects the streamed frames of a request until its final reply
+async fn receive(client: &mut Client, correlation_id: &str) -> (Vec<Value>, Value) {
+    let mut frames = Vec::new();
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(30), client.next())
+            .await
+            .expect("No reply in time")
+            .expect("Connection closed")
+            .unwrap();
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let mut message: Value = serde_json::from_str(&text).unwrap();
+        if message["correlationId"] != correlation_id {
+            continue;
+        }
+        if message.get("body").is_some() {
+            return (frames, message);
+        }
+        if let Some(frame) = message.as_object_mut() {
+            frame.remove("url");
+            frame.remove("correlationId");
+        }
+        frames.push(message);
+    }
+}
+
+fn temp_dir() -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let dir = std::env::temp_dir().join(format!(
+        "pipelab-conformance-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Replaces `{tmp}` and `{cargo}` in every string of a case
+fn substitute(value: &Value, tmp: &Path) -> Value {
+    match value {
+        Value::String(text) => Value::String(
+            text.replace("{tmp}", &slash(tmp))
+                .replace("{cargo}", &std::env::var("CARGO").unwrap_or("cargo".into())),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(|v| substitute(v, tmp)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, v)| (key.clone(), substitute(v, tmp)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn slash(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn assert_matches(expected: &Value, actual: &Value, fixture: &str, name: &str) {
+    assert!(
+        matches(expected, actual),
+        "{} > {}: expected\n{:#}\ngot\n{:#}",
+        fixture,
+        name,
+        expected,
+        actual
+    );
+}
+
+fn matches(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::String(placeholder), _) if placeholder == "{any}" => true,
+        (Value::String(placeholder), _) if placeholder == "{string}" => actual.is_string(),
+        (Value::String(placeholder), _) if placeholder == "{number}" => actual.is_number(),
+        (Value::Object(expected), Value::Object(actual)) => {
+            expected.iter().all(|(key, expected)| {
+                actual
+                    .get(key)
+                    .is_some_and(|actual| matches(expected, actual))
+            })
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            expected.len() == actual.len()
+                && expected.iter().zip(actual).all(|(e, a)| matches(e, a))
+        }
+        _ => expected == actual,
+    }
+}
@@ -0,0 +1,58 @@
+// Checked with the rest of the project by `pnpm typecheck` (`pnpm typecheck:node` in CI):
+// the messages a game sends with the @pipelab/core types must be accepted by the Tauri
+// runtime, and its replies must fit what the game expects.
+// The Routes come from the Rust types, see `cargo test --test bindings`.
+
+import type * as Core from '@pipelab/core'
+import type {
+  Routes,
+  SuccessBody
+} from '../../../../assets/tauri/template/app/src-tauri/bindings/pipelab'
+
+type Input<M> = Core.MakeInputOutput<M, 'input'>
+type Output<M> = Core.MakeInputOutput<M, 'output'>
+type Body<T> = T extends { body: infer B } ? B : {}
+
+type Extends<A, B> = [A] extends [B] ? true : false
+/** Fails to compile unless `T` is `true` */
+type Assert<T extends true> = T
+
+/** A game's request for `Url` is a valid body, and the reply fits the Electron one */
+type Compatible<M, Url extends keyof Routes> =
+  Extends<Body<Input<M>>, Routes[Url]['body']> extends true
+    ? Extends<SuccessBody<Routes[Url]['reply']>, Body<Output<M>>>
+    : false
+
+export type Checks = [
+  Assert<Compatible<Core.MessageEngine, '/engine'>>,
+  Assert<Compatible<Core.MessageInfos, '/infos'>>,
+  Assert<Compatible<Core.MessageExit, '/exit'>>,
+  Assert<Compatible<Core.MessageRun, '/run'>>,
+  Assert<Compatible<Core.MessagePaths, '/paths'>>,
+  Assert<Compatible<Core.MessageWriteFile, '/fs/file/write'>>,
+  Assert<Compatible<Core.MessageReadFile, '/fs/file/read'>>,
+  Assert<Compatible<Core.MessageReadFileBinary, '/fs/file/read/binary'>>,
+  Assert<Compatible<Core.MessageCreateFolder, '/fs/folder/create'>>,
+  Assert<Compatible<Core.MessageCopyFile, '/fs/copy'>>,
+  Assert<Compatible<Core.MessageDelete, '/fs/delete'>>,
+  Assert<Compatible<Core.MessageExistFile, '/fs/exist'>>,
+  Assert<Compatible<Core.MessageListFiles, '/fs/list'>>,
+  Assert<Compatible<Core.MessageFileSize, '/fs/file/size'>>,
+  Assert<Compatible<Core.MessageMove, '/fs/move'>>,
+  Assert<Compatible<Core.MessageWindowMaximize, '/window/maximize'>>,
+  Assert<Compatible<Core.MessageWindowMinimize, '/window/minimize'>>,
+  Assert<Compatible<Core.MessageWindowRestore, '/window/restore'>>,
+  Assert<Compatible<Core.MessageWindowUnmaximize, '/window/unmaximize'>>,
+  Assert<Compatible<Core.MessageRequestAttention, '/window/request-attention'>>,
+  Assert<Compatible<Core.MessageSetAlwaysOnTop, '/window/set-always-on-top'>>,
+  Assert<Compatible<Core.MessageSetHeight, '/window/set-height'>>,
+  Assert<Compatible<Core.MessageSetWidth, '/window/set-width'>>,
+  Assert<Compatible<Core.MessageSetMaximumSize, '/window/set-maximum-size'>>,
+  Assert<Compatible<Core.MessageSetMinimumSize, '/window/set-minimum-size'>>,
+  Assert<Compatible<Core.MessageSetResizable, '/window/set-resizable'>>,
+  Assert<Compatible<Core.MessageSetTitle, '/window/set-title'>>,
+  Assert<Compatible<Core.MessageSetX, '/window/set-x'>>,
+  Assert<Compatible<Core.MessageSetY, '/window/set-y'>>,
+  Assert<Compatible<Core.MessageShowDevTools, '/window/show-dev-tools'>>,
+  Assert<Compatible<Core.MessageSetFullscreen, '/window/set-fullscreen'>>
+]